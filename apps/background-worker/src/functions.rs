@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use apalis::prelude::Error;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use entities::{
     prelude::TelegramBotMusicShareReaction,
     telegram_bot_music_share_reaction::{self, SentimentResponseMood},
@@ -12,8 +12,20 @@ use openai_api_rs::v1::{
         chat_completion::ChatCompletionRequest, ChatCompletionMessage, Content, MessageRole,
     },
 };
-use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::{
+    ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
 use serde::Deserialize;
+use services::{
+    MusicLinkError, WrappedChat, WrappedPeriod, chat_digest, due_digests, format_chat_digest,
+    format_wrapped, mark_digest_sent, render_wrapped_card, wrapped_chats, wrapped_recap,
+};
+use teloxide::{
+    Bot, RequestError,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, InputFile, LinkPreviewOptions, ParseMode, UserId},
+};
 use uuid::Uuid;
 
 use crate::AppState;
@@ -123,3 +135,185 @@ pub async fn rate_unrated_reactions(state: &AppState) -> Result<(), Error> {
     }
     Ok(())
 }
+
+pub async fn refresh_stale_music_links(state: &AppState) -> Result<(), Error> {
+    let config = state.config.music_link_refresh_config();
+    let summary = match state
+        .music_link_service
+        .refresh_stale_music_links(&config, &state.db)
+        .await
+    {
+        Ok(summary) => summary,
+        Err(e) => {
+            return Err(Error::Failed(Arc::new(format!("Error: {e}").into())));
+        }
+    };
+    tracing::info!("Refreshed music links: {summary:?}");
+    state.music_link_service.log_upstream_stats();
+    Ok(())
+}
+
+pub async fn check_link_availability(state: &AppState) -> Result<(), Error> {
+    let config = state.config.link_availability_config();
+    let summary = match state
+        .music_link_service
+        .check_link_availability(&config, &state.db)
+        .await
+    {
+        Ok(summary) => summary,
+        Err(e) => {
+            return Err(Error::Failed(Arc::new(format!("Error: {e}").into())));
+        }
+    };
+    tracing::info!("Checked link availability: {summary:?}");
+    state.music_link_service.log_upstream_stats();
+    Ok(())
+}
+
+/// Why a digest or recap could not be posted into a chat.
+#[derive(Debug, thiserror::Error)]
+enum PostError {
+    #[error(transparent)]
+    Stats(#[from] MusicLinkError),
+    #[error("telegram request failed: {0}")]
+    Telegram(#[from] RequestError),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct PostSummary {
+    sent: u64,
+    /// Chats that shared nothing over the period.
+    empty: u64,
+    failed: u64,
+}
+
+async fn find_user_name(bot: &Bot, chat_id: ChatId, telegram_user_id: i64) -> Option<String> {
+    let user_id = UserId(telegram_user_id as u64);
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => Some(member.user.full_name()),
+        Err(e) => {
+            tracing::debug!("Failed to look up user {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+/// Posts an HTML formatted message into a chat, without link previews.
+async fn send_html(bot: &Bot, chat_id: ChatId, text: String) -> Result<(), RequestError> {
+    bot.send_message(chat_id, text)
+        .parse_mode(ParseMode::Html)
+        .link_preview_options(LinkPreviewOptions {
+            is_disabled: true,
+            url: None,
+            prefer_small_media: false,
+            prefer_large_media: false,
+            show_above_text: false,
+        })
+        .await?;
+    Ok(())
+}
+
+async fn post_chat_digest(
+    bot: &Bot,
+    telegram_channel_id: i64,
+    now: DateTime<Utc>,
+    db: &DatabaseConnection,
+) -> Result<bool, PostError> {
+    let digest = chat_digest(telegram_channel_id, now, db).await?;
+    if digest.counts.shares == 0 {
+        return Ok(false);
+    }
+    let chat_id = ChatId(telegram_channel_id);
+    let sharer_name = match &digest.most_active_sharer {
+        Some(sharer) => find_user_name(bot, chat_id, sharer.telegram_user_id).await,
+        None => None,
+    };
+    let text = format_chat_digest(&digest, sharer_name.as_deref());
+    send_html(bot, chat_id, text).await?;
+    Ok(true)
+}
+
+pub async fn send_weekly_digests(state: &AppState) -> Result<(), Error> {
+    let Some(bot) = &state.telegram else {
+        tracing::debug!("No Telegram bot token configured, skipping weekly digests");
+        return Ok(());
+    };
+    let now = Utc::now();
+    let due = match due_digests(now, &state.db).await {
+        Ok(due) => due,
+        Err(e) => {
+            return Err(Error::Failed(Arc::new(format!("Error: {e}").into())));
+        }
+    };
+    let mut summary = PostSummary::default();
+    for digest in due {
+        let telegram_channel_id = digest.telegram_channel_id;
+        match post_chat_digest(bot, telegram_channel_id, now, &state.db).await {
+            Ok(true) => summary.sent += 1,
+            Ok(false) => summary.empty += 1,
+            Err(e) => {
+                tracing::warn!("Failed to send digest to {}: {}", telegram_channel_id, e);
+                summary.failed += 1;
+                continue;
+            }
+        }
+        if let Err(e) = mark_digest_sent(digest.settings_id, now, &state.db).await {
+            return Err(Error::Failed(Arc::new(format!("Error: {e}").into())));
+        }
+    }
+    tracing::info!("Sent weekly digests: {summary:?}");
+    Ok(())
+}
+
+async fn post_chat_wrapped(
+    bot: &Bot,
+    chat: WrappedChat,
+    period: WrappedPeriod,
+    db: &DatabaseConnection,
+) -> Result<bool, PostError> {
+    let recap = wrapped_recap(chat.telegram_channel_id, None, period, chat.timezone, db).await?;
+    if recap.counts.shares == 0 {
+        return Ok(false);
+    }
+    let chat_id = ChatId(chat.telegram_channel_id);
+    let mut names = HashMap::new();
+    for id in recap.telegram_user_ids() {
+        if let Some(name) = find_user_name(bot, chat_id, id).await {
+            names.insert(id, name);
+        }
+    }
+    send_html(bot, chat_id, format_wrapped(&recap, &names)).await?;
+    if let Some(card) = render_wrapped_card(&recap, &names) {
+        let photo = InputFile::memory(card).file_name("wrapped.png");
+        bot.send_photo(chat_id, photo).await?;
+    }
+    Ok(true)
+}
+
+pub async fn send_wrapped(state: &AppState) -> Result<(), Error> {
+    let Some(bot) = &state.telegram else {
+        tracing::debug!("No Telegram bot token configured, skipping wrapped recaps");
+        return Ok(());
+    };
+    let period = WrappedPeriod::previous(Utc::now().date_naive());
+    let chats = match wrapped_chats(&state.db).await {
+        Ok(chats) => chats,
+        Err(e) => {
+            return Err(Error::Failed(Arc::new(format!("Error: {e}").into())));
+        }
+    };
+    let mut summary = PostSummary::default();
+    for chat in chats {
+        match post_chat_wrapped(bot, chat, period, &state.db).await {
+            Ok(true) => summary.sent += 1,
+            Ok(false) => summary.empty += 1,
+            Err(e) => {
+                let telegram_channel_id = chat.telegram_channel_id;
+                tracing::warn!("Failed to send wrapped to {}: {}", telegram_channel_id, e);
+                summary.failed += 1;
+            }
+        }
+    }
+    tracing::info!("Sent wrapped recaps for {}: {summary:?}", period.label());
+    Ok(())
+}
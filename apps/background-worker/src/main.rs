@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use apalis::{
     layers::{WorkerBuilderExt, retry::RetryPolicy},
@@ -6,11 +6,19 @@ use apalis::{
 };
 use apalis_cron::{CronContext, CronStream, Schedule};
 use chrono::Local;
-use functions::rate_unrated_reactions;
+use functions::{
+    check_link_availability, rate_unrated_reactions, refresh_stale_music_links,
+    send_weekly_digests, send_wrapped,
+};
 use migrations::MigratorTrait;
 use schematic::{Config, ConfigLoader, validate::not_empty};
 use sea_orm::{Database, DatabaseConnection};
 use serde::Serialize;
+use services::{
+    LinkAvailabilityConfig, MusicLinkRefreshConfig, MusicLinkService, MusicLinkSettings,
+    PartialMusicLinkSettings,
+};
+use teloxide::Bot;
 use tokio::join;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -23,12 +31,65 @@ struct AppConfig {
     database_url: String,
     #[setting(validate = not_empty, env = "LLM_API_TOKEN")]
     llm_api_token: String,
+    #[setting(default = 10, env = "MUSIC_LINK_REFRESH_BUDGET")]
+    music_link_refresh_budget: u64,
+    #[setting(default = 30, env = "MUSIC_LINK_REFRESH_STALE_AFTER_DAYS")]
+    music_link_refresh_stale_after_days: u64,
+    #[setting(default = 7, env = "MUSIC_LINK_REFRESH_MISSING_PLATFORMS_AFTER_DAYS")]
+    music_link_refresh_missing_platforms_after_days: u64,
+    #[setting(default = 90, env = "MUSIC_LINK_REFRESH_ACTIVE_WITHIN_DAYS")]
+    music_link_refresh_active_within_days: u64,
+    #[setting(default = 50, env = "LINK_AVAILABILITY_BUDGET")]
+    link_availability_budget: u64,
+    #[setting(default = 7, env = "LINK_AVAILABILITY_CHECK_AFTER_DAYS")]
+    link_availability_check_after_days: u64,
+    #[setting(env = "TELOXIDE_TOKEN")]
+    teloxide_token: Option<String>,
+    #[setting(env = "TELEGRAM_API_BASE_URL")]
+    telegram_api_base_url: Option<String>,
+    #[setting(nested)]
+    music_link: MusicLinkSettings,
+}
+
+impl AppConfig {
+    fn music_link_refresh_config(&self) -> MusicLinkRefreshConfig {
+        let days = |count: u64| Duration::from_secs(count * 24 * 60 * 60);
+        MusicLinkRefreshConfig {
+            budget: self.music_link_refresh_budget,
+            stale_after: days(self.music_link_refresh_stale_after_days),
+            missing_platforms_after: days(self.music_link_refresh_missing_platforms_after_days),
+            active_within: days(self.music_link_refresh_active_within_days),
+        }
+    }
+
+    fn link_availability_config(&self) -> LinkAvailabilityConfig {
+        LinkAvailabilityConfig {
+            budget: self.link_availability_budget,
+            check_after: Duration::from_secs(
+                self.link_availability_check_after_days * 24 * 60 * 60,
+            ),
+        }
+    }
+
+    /// Digests are only posted when the bot token is configured.
+    fn telegram_bot(&self) -> Result<Option<Bot>, Box<dyn std::error::Error>> {
+        let Some(token) = &self.teloxide_token else {
+            return Ok(None);
+        };
+        let bot = match &self.telegram_api_base_url {
+            Some(url) => Bot::new(token).set_api_url(url.parse()?),
+            None => Bot::new(token),
+        };
+        Ok(Some(bot))
+    }
 }
 
 #[derive(Clone)]
 struct AppState {
     config: AppConfig,
     db: DatabaseConnection,
+    music_link_service: Arc<MusicLinkService>,
+    telegram: Option<Bot>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -43,6 +104,54 @@ async fn background_worker_job(
     rate_unrated_reactions(&state).await
 }
 
+#[derive(Debug, Clone, Default)]
+struct RefreshMusicLinks;
+
+async fn refresh_music_links_job(
+    _job: RefreshMusicLinks,
+    state: Data<AppState>,
+    ctx: CronContext<Local>,
+) -> Result<(), Error> {
+    tracing::info!("Refreshing music links at: {}", ctx.get_timestamp());
+    refresh_stale_music_links(&state).await
+}
+
+#[derive(Debug, Clone, Default)]
+struct CheckLinkAvailability;
+
+async fn check_link_availability_job(
+    _job: CheckLinkAvailability,
+    state: Data<AppState>,
+    ctx: CronContext<Local>,
+) -> Result<(), Error> {
+    tracing::info!("Checking link availability at: {}", ctx.get_timestamp());
+    check_link_availability(&state).await
+}
+
+#[derive(Debug, Clone, Default)]
+struct SendWeeklyDigests;
+
+async fn send_weekly_digests_job(
+    _job: SendWeeklyDigests,
+    state: Data<AppState>,
+    ctx: CronContext<Local>,
+) -> Result<(), Error> {
+    tracing::info!("Sending weekly digests at: {}", ctx.get_timestamp());
+    send_weekly_digests(&state).await
+}
+
+#[derive(Debug, Clone, Default)]
+struct SendWrapped;
+
+async fn send_wrapped_job(
+    _job: SendWrapped,
+    state: Data<AppState>,
+    ctx: CronContext<Local>,
+) -> Result<(), Error> {
+    tracing::info!("Sending wrapped recaps at: {}", ctx.get_timestamp());
+    send_wrapped(&state).await
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(debug_assertions)]
@@ -74,7 +183,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     migrations::Migrator::up(&db, None).await?;
     tracing::info!("Database migrations completed");
 
-    let state = AppState { config, db };
+    let music_link_service = Arc::new(MusicLinkService::from_config(
+        config.music_link.service_config(),
+    ));
+    let telegram = config.telegram_bot()?;
+    let state = AppState {
+        config,
+        db,
+        music_link_service,
+        telegram,
+    };
 
     if args.len() > 1 && args[1] == "trigger" {
         tracing::info!("Trigger argument detected, running rate_unrated_reactions and exiting");
@@ -82,6 +200,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    if args.len() > 1 && args[1] == "refresh" {
+        tracing::info!("Refresh argument detected, running refresh_stale_music_links and exiting");
+        refresh_stale_music_links(&state).await?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "check-links" {
+        tracing::info!(
+            "Check links argument detected, running check_link_availability and exiting"
+        );
+        check_link_availability(&state).await?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "digest" {
+        tracing::info!("Digest argument detected, running send_weekly_digests and exiting");
+        send_weekly_digests(&state).await?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "wrapped" {
+        tracing::info!("Wrapped argument detected, running send_wrapped and exiting");
+        send_wrapped(&state).await?;
+        return Ok(());
+    }
+
     tracing::info!("Starting background worker");
 
     let worker = Monitor::new()
@@ -90,13 +234,57 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .retry(RetryPolicy::retries(3))
                 .enable_tracing()
                 .catch_panic()
-                .data(state)
+                .data(state.clone())
                 .backend(CronStream::new_with_timezone(
                     Schedule::from_str("1 * * * * *").unwrap(),
                     Local,
                 ))
                 .build_fn(background_worker_job),
         )
+        .register(
+            WorkerBuilder::new("refresh-music-links-job")
+                .enable_tracing()
+                .catch_panic()
+                .data(state.clone())
+                .backend(CronStream::new_with_timezone(
+                    Schedule::from_str("0 0 * * * *").unwrap(),
+                    Local,
+                ))
+                .build_fn(refresh_music_links_job),
+        )
+        .register(
+            WorkerBuilder::new("check-link-availability-job")
+                .enable_tracing()
+                .catch_panic()
+                .data(state.clone())
+                .backend(CronStream::new_with_timezone(
+                    Schedule::from_str("0 30 * * * *").unwrap(),
+                    Local,
+                ))
+                .build_fn(check_link_availability_job),
+        )
+        .register(
+            WorkerBuilder::new("send-weekly-digests-job")
+                .enable_tracing()
+                .catch_panic()
+                .data(state.clone())
+                .backend(CronStream::new_with_timezone(
+                    Schedule::from_str("0 */15 * * * *").unwrap(),
+                    Local,
+                ))
+                .build_fn(send_weekly_digests_job),
+        )
+        .register(
+            WorkerBuilder::new("send-wrapped-job")
+                .enable_tracing()
+                .catch_panic()
+                .data(state)
+                .backend(CronStream::new_with_timezone(
+                    Schedule::from_str("0 0 12 1 * *").unwrap(),
+                    Local,
+                ))
+                .build_fn(send_wrapped_job),
+        )
         .run();
 
     tracing::info!("Worker registered and running");
@@ -4,7 +4,7 @@ use anyhow::Result;
 use async_graphql::{EmptyMutation, EmptySubscription, Schema, http::graphiql_source};
 use async_graphql_axum::GraphQL;
 use axum::{
-    Router,
+    Extension, Json, Router,
     response::{self, IntoResponse},
     routing::get,
 };
@@ -14,6 +14,7 @@ use schematic::{Config, ConfigLoader, validate::not_empty};
 use sea_orm::Database;
 use serde::Serialize;
 use service::Service;
+use services::{MusicLinkService, MusicLinkSettings, PartialMusicLinkSettings, UpstreamStats};
 use tokio::net::TcpListener;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -26,12 +27,21 @@ mod service;
 struct AppConfig {
     #[setting(validate = not_empty, env = "DATABASE_URL")]
     database_url: String,
+    #[setting(nested)]
+    music_link: MusicLinkSettings,
 }
 
 async fn graphiql() -> impl IntoResponse {
     response::Html(graphiql_source("/", None))
 }
 
+/// Reports how the upstream APIs behind the resolvers are doing.
+async fn upstream_stats(
+    Extension(link_service): Extension<Arc<MusicLinkService>>,
+) -> Json<Vec<UpstreamStats>> {
+    Json(link_service.upstream_stats())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     #[cfg(debug_assertions)]
@@ -58,15 +68,22 @@ async fn main() -> Result<()> {
     tracing::info!("Database migrations completed");
 
     tracing::debug!("Initializing service");
-    let service = Service::new(db).await;
+    let link_service = Arc::new(MusicLinkService::from_config(
+        config.music_link.service_config(),
+    ));
+    let flusher = link_service.spawn_interaction_flusher(db.clone());
+    let service = Arc::new(Service::new(db, link_service.clone()).await);
 
     tracing::debug!("Building GraphQL schema");
     let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
-        .data(Arc::new(service))
+        .data(service.clone())
         .finish();
 
     tracing::debug!("Creating API router");
-    let app = Router::new().route("/", get(graphiql).post_service(GraphQL::new(schema)));
+    let app = Router::new()
+        .route("/", get(graphiql).post_service(GraphQL::new(schema)))
+        .route("/upstreams", get(upstream_stats))
+        .layer(Extension(link_service));
     tracing::debug!("Router setup complete");
 
     tracing::debug!("Binding TCP listener");
@@ -74,12 +91,18 @@ async fn main() -> Result<()> {
     tracing::info!("Listening on {}", listener.local_addr()?);
 
     tracing::debug!("Starting Axum server");
-    let server_result = axum::serve(listener, app).await;
+    let server_result = axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await;
 
     match &server_result {
         Ok(_) => tracing::info!("Server shutdown gracefully"),
         Err(e) => tracing::error!("Server error: {}", e),
     }
+    flusher.abort();
+    service.shutdown().await;
 
     server_result.map_err(Into::into)
 }
@@ -1,4 +1,4 @@
-use async_graphql::{Enum, InputObject, SimpleObject};
+use async_graphql::{Enum, Error, ErrorExtensions, InputObject, SimpleObject};
 use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
@@ -12,22 +12,73 @@ pub mod graphql {
         pub user_country: String,
     }
 
+    #[derive(InputObject, Debug)]
+    pub struct SearchMusicInput {
+        /// Free text such as "artist - title".
+        pub query: String,
+        #[graphql(default = "US")]
+        pub user_country: String,
+    }
+
     #[derive(Debug, Serialize, Deserialize, Enum, Clone, Copy, PartialEq, Eq, EnumIter)]
     pub enum ResolveMusicLinkResponseLinkPlatform {
         Spotify,
         AppleMusic,
         YoutubeMusic,
+        Youtube,
+        Deezer,
+        Tidal,
+        Soundcloud,
+        AmazonMusic,
+        AmazonStore,
+        Bandcamp,
+        Pandora,
+        Napster,
+        Audiomack,
+        Anghami,
+        Boomplay,
+        Audius,
+        Yandex,
+        Spinrilla,
+        Itunes,
+        Google,
+        GoogleStore,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Enum, Clone, Copy, PartialEq, Eq, EnumIter)]
+    pub enum ResolveMusicLinkResponseKind {
+        Song,
+        Album,
+        Artist,
+        Playlist,
     }
 
     #[derive(SimpleObject, Debug)]
     pub struct ResolveMusicLinkResponseLink {
         pub link: Option<String>,
         pub platform: ResolveMusicLinkResponseLinkPlatform,
+        /// How sure the match is, from 0 to 100.
+        pub confidence: u8,
+        /// Whether the link was found by searching rather than an exact match.
+        pub best_guess: bool,
+    }
+
+    #[derive(SimpleObject, Debug)]
+    pub struct ResolveMusicLinkResponseMetadata {
+        pub upc: Option<String>,
+        pub isrc: Option<String>,
+        pub album: Option<String>,
+        pub title: Option<String>,
+        pub artists: Vec<String>,
+        pub artwork_url: Option<String>,
+        pub duration_ms: Option<i32>,
     }
 
     #[derive(SimpleObject, Debug)]
     pub struct ResolveMusicLinkResponse {
         pub found: u8,
+        pub kind: ResolveMusicLinkResponseKind,
+        pub metadata: ResolveMusicLinkResponseMetadata,
         pub collected_links: Vec<ResolveMusicLinkResponseLink>,
     }
 }
@@ -49,17 +100,118 @@ pub fn convert_to_graphql_response(
                 services::MusicPlatform::YoutubeMusic => {
                     graphql::ResolveMusicLinkResponseLinkPlatform::YoutubeMusic
                 }
+                services::MusicPlatform::Youtube => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Youtube
+                }
+                services::MusicPlatform::Deezer => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Deezer
+                }
+                services::MusicPlatform::Tidal => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Tidal
+                }
+                services::MusicPlatform::Soundcloud => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Soundcloud
+                }
+                services::MusicPlatform::AmazonMusic => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::AmazonMusic
+                }
+                services::MusicPlatform::AmazonStore => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::AmazonStore
+                }
+                services::MusicPlatform::Bandcamp => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Bandcamp
+                }
+                services::MusicPlatform::Pandora => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Pandora
+                }
+                services::MusicPlatform::Napster => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Napster
+                }
+                services::MusicPlatform::Audiomack => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Audiomack
+                }
+                services::MusicPlatform::Anghami => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Anghami
+                }
+                services::MusicPlatform::Boomplay => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Boomplay
+                }
+                services::MusicPlatform::Audius => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Audius
+                }
+                services::MusicPlatform::Yandex => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Yandex
+                }
+                services::MusicPlatform::Spinrilla => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Spinrilla
+                }
+                services::MusicPlatform::Itunes => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Itunes
+                }
+                services::MusicPlatform::Google => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::Google
+                }
+                services::MusicPlatform::GoogleStore => {
+                    graphql::ResolveMusicLinkResponseLinkPlatform::GoogleStore
+                }
             };
 
             graphql::ResolveMusicLinkResponseLink {
                 platform,
+                best_guess: link.is_best_guess(),
+                confidence: link.confidence,
                 link: link.link,
             }
         })
         .collect();
 
+    let kind = match service_response.kind {
+        services::MusicEntityKind::Song => graphql::ResolveMusicLinkResponseKind::Song,
+        services::MusicEntityKind::Album => graphql::ResolveMusicLinkResponseKind::Album,
+        services::MusicEntityKind::Artist => graphql::ResolveMusicLinkResponseKind::Artist,
+        services::MusicEntityKind::Playlist => graphql::ResolveMusicLinkResponseKind::Playlist,
+    };
+    let metadata = service_response.metadata;
+
     graphql::ResolveMusicLinkResponse {
         found: service_response.found,
+        kind,
+        metadata: graphql::ResolveMusicLinkResponseMetadata {
+            upc: metadata.upc,
+            isrc: metadata.isrc,
+            album: metadata.album,
+            title: metadata.title,
+            artists: metadata.artists,
+            artwork_url: metadata.artwork_url,
+            duration_ms: metadata.duration_ms,
+        },
         collected_links,
     }
 }
+
+pub fn convert_to_graphql_error(service_error: services::MusicLinkError) -> Error {
+    let code = service_error.code();
+    let retry_after = match &service_error {
+        services::MusicLinkError::RateLimited { retry_after } => *retry_after,
+        _ => None,
+    };
+    // Upstream and database errors carry driver and http details that are
+    // only meant for the logs.
+    let message = match &service_error {
+        services::MusicLinkError::UpstreamUnavailable(_) => {
+            tracing::error!("Upstream error: {}", service_error);
+            "Music service temporarily unavailable".to_owned()
+        }
+        services::MusicLinkError::Database(_) => {
+            tracing::error!("Database error: {}", service_error);
+            "Internal server error".to_owned()
+        }
+        _ => service_error.to_string(),
+    };
+    Error::new(message).extend_with(|_, extensions| {
+        extensions.set("code", code);
+        if let Some(retry_after) = retry_after {
+            extensions.set("retryAfterSeconds", retry_after.as_secs());
+        }
+    })
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use async_graphql::{Context, Object, Result};
 
 use crate::{
-    models::graphql::{ResolveMusicLinkInput, ResolveMusicLinkResponse},
+    models::graphql::{ResolveMusicLinkInput, ResolveMusicLinkResponse, SearchMusicInput},
     service::Service,
 };
 
@@ -36,4 +36,18 @@ impl QueryRoot {
 
         result
     }
+
+    async fn search_music(
+        &self,
+        gql_ctx: &Context<'_>,
+        input: SearchMusicInput,
+    ) -> Result<ResolveMusicLinkResponse> {
+        tracing::info!("GraphQL searching music");
+        let service = gql_ctx.data_unchecked::<Arc<Service>>();
+        let result = service.search_music(input).await;
+        if let Err(e) = &result {
+            tracing::error!("GraphQL resolver encountered an error: {:?}", e);
+        }
+        result
+    }
 }
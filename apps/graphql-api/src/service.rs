@@ -1,16 +1,27 @@
+use std::sync::Arc;
+
 use async_graphql::Result;
 use sea_orm::DatabaseConnection;
+use services::MusicLinkService;
 
-use crate::models::graphql::{ResolveMusicLinkInput, ResolveMusicLinkResponse};
+use crate::models::graphql::{ResolveMusicLinkInput, ResolveMusicLinkResponse, SearchMusicInput};
 
 pub struct Service {
     db: DatabaseConnection,
+    link_service: Arc<MusicLinkService>,
 }
 
 impl Service {
-    pub async fn new(db: DatabaseConnection) -> Self {
+    pub async fn new(db: DatabaseConnection, link_service: Arc<MusicLinkService>) -> Self {
         tracing::debug!("Initializing GraphQL API service");
-        Self { db }
+        Self { db, link_service }
+    }
+
+    /// Writes the interactions the music link service batched up so far.
+    pub async fn shutdown(&self) {
+        if let Err(e) = self.link_service.flush_interactions(&self.db).await {
+            tracing::warn!("Failed to flush music link interactions: {}", e);
+        }
     }
 
     pub async fn resolve_music_link(
@@ -23,15 +34,14 @@ impl Service {
         );
         tracing::debug!("User country: {}", input.user_country);
 
-        let link_service = services::MusicLinkService::new().await;
-
         let service_input = services::MusicLinkInput {
             link: input.link.clone(),
             user_country: input.user_country.clone(),
         };
 
         tracing::debug!("Calling service to resolve music link");
-        let result = match link_service
+        let result = match self
+            .link_service
             .resolve_music_link(service_input, &self.db)
             .await
         {
@@ -44,7 +54,7 @@ impl Service {
             }
             Err(e) => {
                 tracing::warn!("Failed to resolve music link: {}", e);
-                return Err(e.into());
+                return Err(crate::models::convert_to_graphql_error(e));
             }
         };
 
@@ -58,4 +68,29 @@ impl Service {
         );
         Ok(response)
     }
+
+    pub async fn search_music(&self, input: SearchMusicInput) -> Result<ResolveMusicLinkResponse> {
+        tracing::info!("Received music search request for: {}", input.query);
+
+        let service_input = services::MusicSearchInput {
+            query: input.query.clone(),
+            user_country: input.user_country.clone(),
+        };
+
+        let result = match self.link_service.search(service_input, &self.db).await {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Failed to search music: {}", e);
+                return Err(crate::models::convert_to_graphql_error(e));
+            }
+        };
+
+        let response = crate::models::convert_to_graphql_response(result);
+        tracing::info!(
+            "Returning GraphQL response for search: {} with {} platforms",
+            input.query,
+            response.found
+        );
+        Ok(response)
+    }
 }
@@ -1,45 +1,66 @@
 use std::{collections::HashSet, sync::Arc};
 
-use chrono::Utc;
-use convert_case::{Case, Casing};
+use chrono::{DateTime, Utc};
 use entities::{
-    prelude::{TelegramBotChannel, TelegramBotMusicShare, TelegramBotUser},
-    telegram_bot_channel, telegram_bot_music_share, telegram_bot_music_share_reaction,
-    telegram_bot_user,
+    prelude::{
+        TelegramBotChannel, TelegramBotMusicShare, TelegramBotMusicShareReaction, TelegramBotUser,
+    },
+    telegram_bot_channel,
+    telegram_bot_channel_settings::DuplicateShareMode,
+    telegram_bot_music_share, telegram_bot_music_share_reaction, telegram_bot_user,
 };
 use regex::Regex;
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder,
+};
+use services::{
+    DEFAULT_COUNTRY, MusicEntityKind, MusicLinkError, MusicLinkInput, MusicLinkResponse,
+    MusicLinkService, MusicSearchInput, country_from_language_code, normalize_country_code,
 };
-use services::{MusicLinkInput, MusicLinkService};
 use teloxide::{
-    types::{Message, MessageReactionUpdated},
-    utils::html::{link, user_mention},
+    Bot,
+    prelude::Requester,
+    types::{
+        ChatId, Message, MessageEntityKind, MessageId, MessageOrigin, MessageReactionUpdated, User,
+        UserId,
+    },
+    utils::html::{escape, link, user_mention},
 };
 use uuid::Uuid;
 
+use crate::{
+    i18n::Texts,
+    settings::{ChatSettings, platform_name},
+};
+
+pub async fn find_or_create_telegram_channel(
+    telegram_channel_id: i64,
+    db: &DatabaseConnection,
+) -> Result<telegram_bot_channel::Model, DbErr> {
+    let existing_channel = TelegramBotChannel::find()
+        .filter(telegram_bot_channel::Column::TelegramChannelId.eq(telegram_channel_id))
+        .one(db)
+        .await?;
+    if let Some(channel) = existing_channel {
+        let mut updated: telegram_bot_channel::ActiveModel = channel.clone().into();
+        updated.last_interacted_at = ActiveValue::Set(Utc::now());
+        updated.update(db).await?;
+        return Ok(channel);
+    }
+    let new_channel = telegram_bot_channel::ActiveModel {
+        telegram_channel_id: ActiveValue::Set(telegram_channel_id),
+        ..Default::default()
+    };
+    new_channel.insert(db).await
+}
+
 async fn find_or_create_telegram_user(
     user_id: i64,
     db: &DatabaseConnection,
     telegram_channel_id: i64,
 ) -> Result<telegram_bot_user::Model, DbErr> {
-    let channel = 'chan: {
-        let existing_channel = TelegramBotChannel::find()
-            .filter(telegram_bot_channel::Column::TelegramChannelId.eq(telegram_channel_id))
-            .one(db)
-            .await?;
-        if let Some(channel) = existing_channel {
-            let mut updated: telegram_bot_channel::ActiveModel = channel.clone().into();
-            updated.last_interacted_at = ActiveValue::Set(Utc::now());
-            updated.update(db).await?;
-            break 'chan channel;
-        }
-        let new_channel = telegram_bot_channel::ActiveModel {
-            telegram_channel_id: ActiveValue::Set(telegram_channel_id),
-            ..Default::default()
-        };
-        new_channel.insert(db).await?
-    };
+    let channel = find_or_create_telegram_channel(telegram_channel_id, db).await?;
     tracing::debug!("Found or created channel: {}", channel.telegram_channel_id);
     let user = TelegramBotUser::find()
         .filter(telegram_bot_user::Column::TelegramUserId.eq(user_id))
@@ -61,39 +82,464 @@ async fn find_or_create_telegram_user(
     Ok(result)
 }
 
+/// Picks the storefront for links shared in `msg`: the sender's own choice,
+/// then the chat default, then the region of their Telegram language.
+async fn get_user_country(msg: &Message, db: &DatabaseConnection) -> Result<String, DbErr> {
+    let channel = TelegramBotChannel::find()
+        .filter(telegram_bot_channel::Column::TelegramChannelId.eq(msg.chat.id.0))
+        .one(db)
+        .await?;
+    let user = match (&msg.from, &channel) {
+        (Some(from), Some(channel)) => {
+            TelegramBotUser::find()
+                .filter(telegram_bot_user::Column::TelegramUserId.eq(from.id.0 as i64))
+                .filter(telegram_bot_user::Column::TelegramBotChannelId.eq(channel.id))
+                .one(db)
+                .await?
+        }
+        _ => None,
+    };
+    let inferred = msg
+        .from
+        .as_ref()
+        .and_then(|from| from.language_code.as_deref())
+        .and_then(country_from_language_code)
+        .map(str::to_owned);
+    let country = user
+        .and_then(|user| user.country)
+        .or_else(|| channel.and_then(|channel| channel.country))
+        .or(inferred)
+        .unwrap_or_else(|| DEFAULT_COUNTRY.to_owned());
+    tracing::debug!("Using storefront country {}", country);
+    Ok(country)
+}
+
+/// Whether a country command changes the sender's storefront or the chat default.
+#[derive(Debug, Clone, Copy)]
+pub enum CountryScope {
+    User,
+    Chat,
+}
+
+/// Shows, sets or with `reset` clears a storefront country and returns the reply.
+pub async fn process_country_command(
+    argument: String,
+    msg: &Message,
+    db: &DatabaseConnection,
+    scope: CountryScope,
+) -> Result<String, DbErr> {
+    let argument = argument.trim();
+    if argument.is_empty() {
+        return Ok(match scope {
+            CountryScope::User => {
+                let country = get_user_country(msg, db).await?;
+                format!("Your links are shown for the {} storefront.", country)
+            }
+            CountryScope::Chat => {
+                let channel = TelegramBotChannel::find()
+                    .filter(telegram_bot_channel::Column::TelegramChannelId.eq(msg.chat.id.0))
+                    .one(db)
+                    .await?;
+                match channel.and_then(|channel| channel.country) {
+                    Some(country) => format!("This chat defaults to the {} storefront.", country),
+                    None => "This chat has no default storefront.".to_string(),
+                }
+            }
+        });
+    }
+    let country = if argument.eq_ignore_ascii_case("reset") {
+        None
+    } else {
+        match normalize_country_code(argument) {
+            Some(country) => Some(country.to_owned()),
+            None => {
+                return Ok(format!(
+                    "{} is not a two letter country code such as US or GB.",
+                    escape(argument)
+                ));
+            }
+        }
+    };
+    match scope {
+        CountryScope::User => {
+            let Some(from) = &msg.from else {
+                return Ok("Only users can pick a storefront.".to_string());
+            };
+            let user = find_or_create_telegram_user(from.id.0 as i64, db, msg.chat.id.0).await?;
+            let mut update: telegram_bot_user::ActiveModel = user.into();
+            update.country = ActiveValue::Set(country.clone());
+            update.update(db).await?;
+        }
+        CountryScope::Chat => {
+            let channel = find_or_create_telegram_channel(msg.chat.id.0, db).await?;
+            let mut update: telegram_bot_channel::ActiveModel = channel.into();
+            update.country = ActiveValue::Set(country.clone());
+            update.update(db).await?;
+        }
+    }
+    Ok(match (scope, country) {
+        (CountryScope::User, Some(country)) => {
+            format!("Your links will use the {} storefront.", country)
+        }
+        (CountryScope::Chat, Some(country)) => {
+            format!("Links in this chat will use the {} storefront.", country)
+        }
+        (CountryScope::User, None) => "Your storefront now follows the chat default.".to_string(),
+        (CountryScope::Chat, None) => "This chat no longer has a default storefront.".to_string(),
+    })
+}
+
 pub enum ProcessMessageResponse {
     NoUrlDetected,
     HasUrlNoMusicLinksFound,
+    HasUrlUpstreamUnavailable,
     HasUrlMusicLinksFound {
         text: String,
         music_link_ids: Vec<Uuid>,
+        /// The bot message of an earlier share to reply to instead.
+        reply_to: Option<MessageId>,
     },
+    /// Every link was shared in the chat before and the chat rejects duplicates.
+    HasUrlAlreadyShared {
+        text: String,
+    },
+}
+
+/// The first share of a music link in a chat.
+struct FirstShare {
+    telegram_user_id: i64,
+    shared_at: DateTime<Utc>,
+    reactions: u64,
+    sent_telegram_message_id: i64,
 }
 
-fn get_regex_for_url() -> Regex {
+async fn find_first_share(
+    music_link_id: Uuid,
+    telegram_channel_id: i64,
+    db: &DatabaseConnection,
+) -> Result<Option<FirstShare>, DbErr> {
+    let channel = TelegramBotChannel::find()
+        .filter(telegram_bot_channel::Column::TelegramChannelId.eq(telegram_channel_id))
+        .one(db)
+        .await?;
+    let Some(channel) = channel else {
+        return Ok(None);
+    };
+    let first_share = TelegramBotMusicShare::find()
+        .find_also_related(TelegramBotUser)
+        .filter(telegram_bot_music_share::Column::MusicLinkId.eq(music_link_id))
+        .filter(telegram_bot_user::Column::TelegramBotChannelId.eq(channel.id))
+        .order_by_asc(telegram_bot_music_share::Column::CreatedAt)
+        .one(db)
+        .await?;
+    let Some((share, Some(user))) = first_share else {
+        return Ok(None);
+    };
+    let reactions = TelegramBotMusicShareReaction::find()
+        .filter(telegram_bot_music_share_reaction::Column::TelegramBotMusicShareId.eq(share.id))
+        .count(db)
+        .await?;
+    Ok(Some(FirstShare {
+        telegram_user_id: user.telegram_user_id,
+        shared_at: share.created_at,
+        reactions,
+        sent_telegram_message_id: share.sent_telegram_message_id,
+    }))
+}
+
+/// Looks up a member of a chat, who may have left it since.
+pub async fn find_chat_user(bot: &Bot, chat_id: ChatId, telegram_user_id: i64) -> Option<User> {
+    let user_id = UserId(telegram_user_id as u64);
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => Some(member.user),
+        Err(e) => {
+            tracing::debug!("Failed to look up user {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+/// Describes a first share as "<lead> @user on <date>, N reactions".
+async fn describe_first_share(
+    bot: &Bot,
+    msg: &Message,
+    share: &FirstShare,
+    lead: &str,
+    texts: &Texts,
+) -> String {
+    let user_id = UserId(share.telegram_user_id as u64);
+    let name = match find_chat_user(bot, msg.chat.id, share.telegram_user_id).await {
+        Some(user) => user
+            .mention()
+            .unwrap_or_else(|| user_mention(user_id, user.full_name().as_str())),
+        None => user_mention(user_id, "?"),
+    };
+    let reactions = if share.reactions == 1 {
+        texts.reaction
+    } else {
+        texts.reactions
+    };
+    format!(
+        "{} {} {} {}, {} {}",
+        lead,
+        name,
+        texts.shared_on,
+        share.shared_at.format("%Y-%m-%d"),
+        share.reactions,
+        reactions
+    )
+}
+
+/// What to do with a music link, depending on whether it was shared in the
+/// chat before and how the chat handles duplicates.
+enum DuplicateShare {
+    New,
+    /// A note to add below the links.
+    Annotate(String),
+    ReplyTo(MessageId),
+    /// The notice to send instead of the links.
+    Reject(String),
+}
+
+async fn check_duplicate_share(
+    bot: &Bot,
+    msg: &Message,
+    db: &DatabaseConnection,
+    music_link_id: Uuid,
+    settings: &ChatSettings,
+) -> Result<DuplicateShare, DbErr> {
+    let Some(share) = find_first_share(music_link_id, msg.chat.id.0, db).await? else {
+        return Ok(DuplicateShare::New);
+    };
+    tracing::debug!(
+        "Music link {} was shared in this chat before",
+        music_link_id
+    );
+    let texts = settings.texts();
+    let duplicate = match settings.duplicate_share_mode {
+        DuplicateShareMode::Annotate => {
+            let note = describe_first_share(bot, msg, &share, texts.first_shared_by, texts).await;
+            DuplicateShare::Annotate(note)
+        }
+        DuplicateShareMode::ReplyToOriginal => {
+            match i32::try_from(share.sent_telegram_message_id) {
+                Ok(id) => DuplicateShare::ReplyTo(MessageId(id)),
+                Err(_) => DuplicateShare::New,
+            }
+        }
+        DuplicateShareMode::Reject => {
+            let notice =
+                describe_first_share(bot, msg, &share, texts.already_shared_by, texts).await;
+            DuplicateShare::Reject(notice)
+        }
+    };
+    Ok(duplicate)
+}
+
+pub fn get_regex_for_url() -> Regex {
     Regex::new(r"https?://[^\s]+").unwrap()
 }
 
+/// The text of a message, or the caption of a media message.
+fn get_text_or_caption(message: &Message) -> &str {
+    message
+        .text()
+        .or_else(|| message.caption())
+        .unwrap_or_default()
+}
+
+/// Collects the urls in the text or caption of `message` in order and without
+/// duplicates, including links hidden behind text.
+///
+/// Telegram marks the urls it detects as entities, so the text is only scanned
+/// when there are none, such as for messages sent without entity parsing.
+fn get_urls_in_message(message: &Message) -> Vec<String> {
+    let entities = message
+        .parse_entities()
+        .or_else(|| message.parse_caption_entities())
+        .unwrap_or_default();
+    let mut urls: Vec<String> = entities
+        .iter()
+        .filter_map(|entity| match entity.kind() {
+            MessageEntityKind::Url if entity.text().contains("://") => {
+                Some(entity.text().to_string())
+            }
+            MessageEntityKind::Url => Some(format!("https://{}", entity.text())),
+            MessageEntityKind::TextLink { url } if matches!(url.scheme(), "http" | "https") => {
+                Some(url.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    if urls.is_empty() {
+        urls = get_regex_for_url()
+            .find_iter(get_text_or_caption(message))
+            .map(|m| m.as_str().to_string())
+            .collect();
+    }
+    let mut seen = HashSet::new();
+    urls.retain(|url| seen.insert(url.clone()));
+    urls
+}
+
+/// Removes the visible urls from the text or caption of `message`, leaving
+/// whatever the sender wrote around them.
+fn get_comment_in_message(message: &Message) -> String {
+    let entities = message
+        .parse_entities()
+        .or_else(|| message.parse_caption_entities())
+        .unwrap_or_default();
+    let mut comment = get_text_or_caption(message).to_string();
+    for entity in entities.iter().rev() {
+        if matches!(entity.kind(), MessageEntityKind::Url) {
+            comment.replace_range(entity.range(), "");
+        }
+    }
+    get_regex_for_url()
+        .replace_all(comment.trim(), "")
+        .trim()
+        .to_string()
+}
+
 pub fn has_url_in_message(message: Message) -> bool {
-    let url_regex = get_regex_for_url();
-    url_regex.find(message.text().unwrap_or_default()).is_some()
+    !get_urls_in_message(&message).is_empty()
 }
 
 pub fn is_reply_to_message(message: Message) -> bool {
     message.reply_to_message().is_some()
 }
 
+fn get_heading_for_music_link(result: &MusicLinkResponse, url: &str, texts: &Texts) -> String {
+    let metadata = &result.metadata;
+    let artists = metadata.artists.join(", ");
+    let heading = match (result.kind, &metadata.title) {
+        (MusicEntityKind::Song, Some(title)) if !artists.is_empty() => {
+            format!("{} – {}", artists, title)
+        }
+        (MusicEntityKind::Album, Some(title)) if !artists.is_empty() => {
+            format!("{}: {} – {}", texts.album, artists, title)
+        }
+        (MusicEntityKind::Album, Some(title)) => format!("{}: {}", texts.album, title),
+        (MusicEntityKind::Artist, Some(name)) => format!("{}: {}", texts.artist, name),
+        (MusicEntityKind::Playlist, Some(title)) => format!("{}: {}", texts.playlist, title),
+        (MusicEntityKind::Song, Some(title)) => title.clone(),
+        (MusicEntityKind::Song, None) => return format!("{} {}", texts.link_for, url),
+        (kind, None) => {
+            let label = match kind {
+                MusicEntityKind::Album => texts.album,
+                MusicEntityKind::Artist => texts.artist,
+                _ => texts.playlist,
+            };
+            return format!("{} {} {}", label, texts.link_for, url);
+        }
+    };
+    escape(&heading)
+}
+
+/// Formats the heading and platform links for a resolved music link, or `None`
+/// when none of the platforms the chat shows were found.
+pub fn format_music_link(
+    result: &MusicLinkResponse,
+    source: &str,
+    settings: &ChatSettings,
+) -> Option<String> {
+    if result.found == 0 {
+        return None;
+    }
+    tracing::debug!(
+        "Processing {} music platforms",
+        result.collected_links.len()
+    );
+    let texts = settings.texts();
+    let platforms: Vec<_> = settings
+        .platforms
+        .iter()
+        .filter_map(|platform| {
+            let music_link = result
+                .collected_links
+                .iter()
+                .find(|music_link| music_link.platform == *platform)?;
+            let platform = platform_name(*platform);
+            music_link.link.as_ref().map(|found_link| {
+                tracing::debug!("Found {} link: {}", platform, found_link);
+                if music_link.is_best_guess() {
+                    format!("{} ({})", link(found_link, &platform), texts.best_guess)
+                } else {
+                    link(found_link, &platform)
+                }
+            })
+        })
+        .collect();
+    if platforms.is_empty() {
+        return None;
+    }
+    let heading = get_heading_for_music_link(result, source, texts);
+    Some(format!("{}\n{}", heading, platforms.join(", ")))
+}
+
+/// Names where a forwarded message originally came from, linking to public
+/// channel posts.
+fn get_forward_origin(msg: &Message) -> Option<String> {
+    let origin = match msg.forward_origin()? {
+        MessageOrigin::User { sender_user, .. } => escape(&sender_user.full_name()),
+        MessageOrigin::HiddenUser {
+            sender_user_name, ..
+        } => escape(sender_user_name),
+        MessageOrigin::Chat { sender_chat, .. } => escape(sender_chat.title().unwrap_or("a chat")),
+        MessageOrigin::Channel {
+            chat, message_id, ..
+        } => {
+            let title = chat.title().unwrap_or("a channel");
+            match chat.username() {
+                Some(username) => link(
+                    &format!("https://t.me/{}/{}", username, message_id.0),
+                    title,
+                ),
+                None => escape(title),
+            }
+        }
+    };
+    Some(origin)
+}
+
+/// Credits the sender of `msg`, quoting whatever `comment` they added.
+fn append_attribution(response: &mut String, msg: &Message, comment: &str, texts: &Texts) {
+    let Some(user) = &msg.from else {
+        return;
+    };
+    let mut username = user
+        .mention()
+        .unwrap_or_else(|| user_mention(user.id, user.full_name().as_str()));
+    if let Some(origin) = get_forward_origin(msg) {
+        username.push_str(&format!(", {} {}", texts.forwarded_from, origin));
+    }
+    tracing::debug!("Adding attribution for user: {}", user.full_name());
+    if comment.is_empty() {
+        tracing::debug!("Nothing but links in the user message");
+    }
+    response.push_str(&format_attribution(&username, comment, texts));
+}
+
+/// Formats the attribution line for the HTML reply. `username` is markup
+/// already, while `comment` is the sender's plain text and gets escaped.
+fn format_attribution(username: &str, comment: &str, texts: &Texts) -> String {
+    if comment.is_empty() {
+        format!("\n\n{} {}", texts.posted_by, username)
+    } else {
+        format!("\n\n{} {}: {}", texts.posted_by, username, escape(comment))
+    }
+}
+
 pub async fn process_music_share(
-    text: String,
+    bot: &Bot,
     msg: &Message,
     db: Arc<DatabaseConnection>,
+    music_service: &MusicLinkService,
+    settings: &ChatSettings,
 ) -> Result<ProcessMessageResponse, DbErr> {
-    tracing::debug!("Processing message: {}", text);
+    tracing::debug!("Processing message: {}", get_text_or_caption(msg));
 
-    let urls: HashSet<_> = get_regex_for_url()
-        .find_iter(&text)
-        .map(|m| m.as_str().to_string())
-        .collect();
+    let urls = get_urls_in_message(msg);
 
     if urls.is_empty() {
         tracing::debug!("No URLs found in message");
@@ -102,15 +548,17 @@ pub async fn process_music_share(
 
     tracing::debug!("Found {} URLs in message", urls.len());
     let mut response = String::new();
-    let music_service = MusicLinkService::new().await;
-    tracing::debug!("MusicLinkService initialized");
 
+    let user_country = get_user_country(msg, &db).await?;
     let mut music_link_ids = Vec::new();
+    let mut upstream_unavailable = false;
+    let mut reply_to = None;
+    let mut rejected = Vec::new();
     for url in urls {
         tracing::debug!("Processing URL: {}", url);
         let service_input = MusicLinkInput {
             link: url.clone(),
-            user_country: "US".to_string(),
+            user_country: user_country.clone(),
         };
 
         let result = match music_service.resolve_music_link(service_input, &db).await {
@@ -118,38 +566,49 @@ pub async fn process_music_share(
                 tracing::debug!("Successfully resolved music link, found: {}", result.found);
                 result
             }
+            Err(MusicLinkError::Database(e)) => return Err(e),
             Err(e) => {
                 tracing::warn!("Failed to resolve music link for {}: {}", url, e);
+                upstream_unavailable |= e.is_transient();
                 continue;
             }
         };
 
-        music_link_ids.push(result.id);
-
-        if result.found > 0 {
-            tracing::debug!(
-                "Processing {} music platforms",
-                result.collected_links.len()
-            );
-            let platforms: Vec<_> = result
-                .collected_links
-                .iter()
-                .filter_map(|music_link| {
-                    let platform = format!("{:?}", music_link.platform).to_case(Case::Title);
-                    music_link.link.as_ref().map(|found_link| {
-                        tracing::debug!("Found {} link: {}", platform, found_link);
-                        link(found_link, &platform)
-                    })
-                })
-                .collect();
-
-            if !response.is_empty() {
-                response.push_str("\n\n");
-            }
-            response.push_str(&format!("for {}\n{}", url, platforms.join(", ")));
-        } else {
+        let Some(mut formatted) = format_music_link(&result, &url, settings) else {
             tracing::debug!("No music platforms found for {}", url);
+            music_link_ids.push(result.id);
+            continue;
+        };
+        match check_duplicate_share(bot, msg, &db, result.id, settings).await? {
+            DuplicateShare::New => {}
+            DuplicateShare::Annotate(note) => {
+                formatted.push_str(&format!("\n<i>{}</i>", note));
+            }
+            DuplicateShare::ReplyTo(message_id) => {
+                reply_to.get_or_insert(message_id);
+            }
+            DuplicateShare::Reject(notice) => {
+                rejected.push(notice);
+                continue;
+            }
+        }
+        music_link_ids.push(result.id);
+        if !response.is_empty() {
+            response.push_str("\n\n");
         }
+        response.push_str(&formatted);
+    }
+
+    if response.is_empty() && !rejected.is_empty() {
+        tracing::debug!("Every music link was shared before");
+        return Ok(ProcessMessageResponse::HasUrlAlreadyShared {
+            text: rejected.join("\n"),
+        });
+    }
+
+    if response.is_empty() && upstream_unavailable {
+        tracing::debug!("Upstream unavailable for at least one URL");
+        return Ok(ProcessMessageResponse::HasUrlUpstreamUnavailable);
     }
 
     if response.is_empty() {
@@ -157,30 +616,59 @@ pub async fn process_music_share(
         return Ok(ProcessMessageResponse::HasUrlNoMusicLinksFound);
     }
 
-    if let Some(user) = &msg.from {
-        let username = user
-            .mention()
-            .unwrap_or_else(|| user_mention(user.id, user.full_name().as_str()));
-        tracing::debug!("Adding attribution for user: {}", user.full_name());
-        let cleaned_text = get_regex_for_url()
-            .replace_all(text.trim(), "")
-            .into_owned();
-        if !cleaned_text.trim().is_empty() {
-            response.push_str(&format!(
-                "\n\nPosted by {}: {}",
-                username,
-                cleaned_text.trim()
-            ));
-        } else {
-            tracing::debug!("Only URL contained in the user message");
-            response.push_str(&format!("\n\nPosted by {}", username));
-        }
-    }
+    let comment = get_comment_in_message(msg);
+    append_attribution(&mut response, msg, &comment, settings.texts());
 
     tracing::debug!("Returning response with {} characters", response.len());
     Ok(ProcessMessageResponse::HasUrlMusicLinksFound {
         music_link_ids,
         text: response,
+        reply_to,
+    })
+}
+
+pub async fn process_find_command(
+    bot: &Bot,
+    query: String,
+    msg: &Message,
+    db: Arc<DatabaseConnection>,
+    music_service: &MusicLinkService,
+    settings: &ChatSettings,
+) -> Result<ProcessMessageResponse, DbErr> {
+    tracing::debug!("Searching for: {}", query);
+    let service_input = MusicSearchInput {
+        query: query.clone(),
+        user_country: get_user_country(msg, &db).await?,
+    };
+    let result = match music_service.search(service_input, &db).await {
+        Ok(result) => result,
+        Err(MusicLinkError::Database(e)) => return Err(e),
+        Err(e) if e.is_transient() => {
+            tracing::warn!("Failed to search for {}: {}", query, e);
+            return Ok(ProcessMessageResponse::HasUrlUpstreamUnavailable);
+        }
+        Err(e) => {
+            tracing::debug!("No match found for {}: {}", query, e);
+            return Ok(ProcessMessageResponse::HasUrlNoMusicLinksFound);
+        }
+    };
+    let Some(mut response) = format_music_link(&result, &query, settings) else {
+        return Ok(ProcessMessageResponse::HasUrlNoMusicLinksFound);
+    };
+    let mut reply_to = None;
+    match check_duplicate_share(bot, msg, &db, result.id, settings).await? {
+        DuplicateShare::New => {}
+        DuplicateShare::Annotate(note) => response.push_str(&format!("\n<i>{}</i>", note)),
+        DuplicateShare::ReplyTo(message_id) => reply_to = Some(message_id),
+        DuplicateShare::Reject(text) => {
+            return Ok(ProcessMessageResponse::HasUrlAlreadyShared { text });
+        }
+    }
+    append_attribution(&mut response, msg, "", settings.texts());
+    Ok(ProcessMessageResponse::HasUrlMusicLinksFound {
+        music_link_ids: vec![result.id],
+        text: response,
+        reply_to,
     })
 }
 
@@ -285,3 +773,30 @@ pub async fn process_emoji_reaction(
     .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use entities::telegram_bot_channel_settings::ReplyLanguage;
+
+    use super::*;
+    use crate::i18n::texts;
+
+    #[test]
+    fn attribution_escapes_the_comment() {
+        let attribution = format_attribution(
+            "<a href=\"tg://user?id=1\">Ann</a>",
+            "a < b & c <b>",
+            texts(ReplyLanguage::En),
+        );
+        assert_eq!(
+            attribution,
+            "\n\nPosted by <a href=\"tg://user?id=1\">Ann</a>: a &lt; b &amp; c &lt;b&gt;"
+        );
+    }
+
+    #[test]
+    fn attribution_without_comment_only_names_the_sender() {
+        let attribution = format_attribution("@ann", "", texts(ReplyLanguage::En));
+        assert_eq!(attribution, "\n\nPosted by @ann");
+    }
+}
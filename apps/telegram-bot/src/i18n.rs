@@ -0,0 +1,119 @@
+use entities::telegram_bot_channel_settings::ReplyLanguage;
+
+/// The phrases the bot uses in replies, in one of the supported languages.
+pub struct Texts {
+    pub album: &'static str,
+    pub artist: &'static str,
+    pub playlist: &'static str,
+    pub link_for: &'static str,
+    pub posted_by: &'static str,
+    pub best_guess: &'static str,
+    pub forwarded_from: &'static str,
+    pub upstream_unavailable: &'static str,
+    pub first_shared_by: &'static str,
+    pub already_shared_by: &'static str,
+    pub shared_on: &'static str,
+    pub reaction: &'static str,
+    pub reactions: &'static str,
+}
+
+static EN: Texts = Texts {
+    album: "Album",
+    artist: "Artist",
+    playlist: "Playlist",
+    link_for: "for",
+    posted_by: "Posted by",
+    best_guess: "best guess",
+    forwarded_from: "forwarded from",
+    upstream_unavailable: "Music services are not responding right now, please try again later.",
+    first_shared_by: "First shared by",
+    already_shared_by: "Already shared by",
+    shared_on: "on",
+    reaction: "reaction",
+    reactions: "reactions",
+};
+
+static DE: Texts = Texts {
+    album: "Album",
+    artist: "Künstler",
+    playlist: "Playlist",
+    link_for: "für",
+    posted_by: "Gepostet von",
+    best_guess: "beste Schätzung",
+    forwarded_from: "weitergeleitet von",
+    upstream_unavailable: "Die Musikdienste antworten gerade nicht, bitte versuche es später noch einmal.",
+    first_shared_by: "Zuerst geteilt von",
+    already_shared_by: "Bereits geteilt von",
+    shared_on: "am",
+    reaction: "Reaktion",
+    reactions: "Reaktionen",
+};
+
+static ES: Texts = Texts {
+    album: "Álbum",
+    artist: "Artista",
+    playlist: "Lista",
+    link_for: "para",
+    posted_by: "Publicado por",
+    best_guess: "mejor estimación",
+    forwarded_from: "reenviado desde",
+    upstream_unavailable: "Los servicios de música no responden en este momento, inténtalo de nuevo más tarde.",
+    first_shared_by: "Compartido primero por",
+    already_shared_by: "Ya compartido por",
+    shared_on: "el",
+    reaction: "reacción",
+    reactions: "reacciones",
+};
+
+static FR: Texts = Texts {
+    album: "Album",
+    artist: "Artiste",
+    playlist: "Playlist",
+    link_for: "pour",
+    posted_by: "Publié par",
+    best_guess: "meilleure estimation",
+    forwarded_from: "transféré depuis",
+    upstream_unavailable: "Les services de musique ne répondent pas pour le moment, réessayez plus tard.",
+    first_shared_by: "Partagé en premier par",
+    already_shared_by: "Déjà partagé par",
+    shared_on: "le",
+    reaction: "réaction",
+    reactions: "réactions",
+};
+
+static PT: Texts = Texts {
+    album: "Álbum",
+    artist: "Artista",
+    playlist: "Playlist",
+    link_for: "para",
+    posted_by: "Publicado por",
+    best_guess: "melhor palpite",
+    forwarded_from: "encaminhado de",
+    upstream_unavailable: "Os serviços de música não estão respondendo agora, tente novamente mais tarde.",
+    first_shared_by: "Compartilhado primeiro por",
+    already_shared_by: "Já compartilhado por",
+    shared_on: "em",
+    reaction: "reação",
+    reactions: "reações",
+};
+
+pub fn texts(language: ReplyLanguage) -> &'static Texts {
+    match language {
+        ReplyLanguage::En => &EN,
+        ReplyLanguage::De => &DE,
+        ReplyLanguage::Es => &ES,
+        ReplyLanguage::Fr => &FR,
+        ReplyLanguage::Pt => &PT,
+    }
+}
+
+/// The name of a language in that language, for the settings keyboard.
+pub fn language_name(language: ReplyLanguage) -> &'static str {
+    match language {
+        ReplyLanguage::En => "English",
+        ReplyLanguage::De => "Deutsch",
+        ReplyLanguage::Es => "Español",
+        ReplyLanguage::Fr => "Français",
+        ReplyLanguage::Pt => "Português",
+    }
+}
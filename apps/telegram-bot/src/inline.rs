@@ -0,0 +1,247 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use entities::{
+    prelude::{TelegramBotChannel, TelegramBotUser},
+    telegram_bot_channel, telegram_bot_inline_share, telegram_bot_user,
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+};
+use services::{
+    DEFAULT_COUNTRY, MusicLinkError, MusicLinkInput, MusicLinkResponse, MusicLinkService,
+    MusicSearchInput, country_from_language_code,
+};
+use teloxide::{
+    Bot, RequestError,
+    payloads::AnswerInlineQuerySetters,
+    prelude::Requester,
+    types::{
+        ChosenInlineResult, InlineQuery, InlineQueryResult, InlineQueryResultArticle,
+        InputMessageContent, InputMessageContentText, ParseMode, User,
+    },
+};
+use uuid::Uuid;
+
+use crate::{
+    functions::{format_music_link, get_regex_for_url},
+    settings::{ChatSettings, platform_name},
+};
+
+/// Shorter queries are most likely still being typed, and searching for them
+/// would only spend the upstream rate limits.
+const INLINE_QUERY_MIN_CHARS: usize = 3;
+/// How long an inline query waits for a link that is not known yet. Resolving
+/// it can queue behind the song.link rate limit for much longer, and by then
+/// the user has usually typed on, so the resolution is given up instead.
+const INLINE_QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Picks the storefront for an inline query, which is not tied to a chat: the
+/// country the user set in their private chat with the bot, then the region of
+/// their Telegram language.
+async fn get_inline_user_country(user: &User, db: &DatabaseConnection) -> Result<String, DbErr> {
+    let private_chat_id = user.id.0 as i64;
+    let stored = TelegramBotUser::find()
+        .inner_join(TelegramBotChannel)
+        .filter(telegram_bot_user::Column::TelegramUserId.eq(private_chat_id))
+        .filter(telegram_bot_channel::Column::TelegramChannelId.eq(private_chat_id))
+        .one(db)
+        .await?
+        .and_then(|user| user.country);
+    let inferred = user
+        .language_code
+        .as_deref()
+        .and_then(country_from_language_code)
+        .map(str::to_owned);
+    Ok(stored
+        .or(inferred)
+        .unwrap_or_else(|| DEFAULT_COUNTRY.to_owned()))
+}
+
+/// Runs `resolving` for at most `timeout`, returning `None` when it does not
+/// finish in time. The task is aborted then, so a query the user has moved on
+/// from stops spending the upstream limits that group chats share.
+async fn resolve_within<T: Send + 'static>(
+    timeout: Duration,
+    resolving: impl Future<Output = T> + Send + 'static,
+) -> Option<T> {
+    let mut handle = tokio::spawn(resolving);
+    match tokio::time::timeout(timeout, &mut handle).await {
+        Ok(Ok(resolved)) => Some(resolved),
+        Ok(Err(e)) => {
+            tracing::error!("Inline query resolution panicked: {}", e);
+            None
+        }
+        Err(_) => {
+            handle.abort();
+            None
+        }
+    }
+}
+
+/// Resolves the first link in `text`, or searches for it when there is none.
+/// Links resolved before are answered right away, anything else gets
+/// `INLINE_QUERY_TIMEOUT` before `None` is returned.
+async fn resolve_inline_query(
+    text: &str,
+    user_country: String,
+    db: Arc<DatabaseConnection>,
+    music_service: Arc<MusicLinkService>,
+) -> Result<Option<MusicLinkResponse>, MusicLinkError> {
+    let link = get_regex_for_url()
+        .find(text)
+        .map(|url| url.as_str().to_owned());
+    if let Some(link) = &link {
+        let input = MusicLinkInput {
+            link: link.clone(),
+            user_country: user_country.clone(),
+        };
+        if let Some(known) = music_service.find_resolved_music_link(input, &db).await? {
+            return Ok(Some(known));
+        }
+    }
+    let query = text.to_owned();
+    let resolving = async move {
+        match link {
+            Some(link) => {
+                let input = MusicLinkInput { link, user_country };
+                music_service.resolve_music_link(input, &db).await
+            }
+            None => {
+                let input = MusicSearchInput {
+                    query,
+                    user_country,
+                };
+                music_service.search(input, &db).await
+            }
+        }
+    };
+    resolve_within(INLINE_QUERY_TIMEOUT, resolving)
+        .await
+        .transpose()
+}
+
+fn get_inline_result_title(result: &MusicLinkResponse, source: &str) -> String {
+    let metadata = &result.metadata;
+    match (&metadata.title, metadata.artists.is_empty()) {
+        (Some(title), false) => format!("{} – {}", metadata.artists.join(", "), title),
+        (Some(title), true) => title.clone(),
+        (None, _) => source.to_owned(),
+    }
+}
+
+/// Builds the result that posts the links for `result`, or `None` when no
+/// platform was found.
+fn get_inline_result(result: &MusicLinkResponse, source: &str) -> Option<InlineQueryResult> {
+    let message = format_music_link(result, source, &ChatSettings::default())?;
+    let found: Vec<_> = result
+        .collected_links
+        .iter()
+        .filter(|music_link| music_link.link.is_some())
+        .map(|music_link| platform_name(music_link.platform))
+        .collect();
+    let content = InputMessageContentText::new(message).parse_mode(ParseMode::Html);
+    let mut article = InlineQueryResultArticle::new(
+        result.id.to_string(),
+        get_inline_result_title(result, source),
+        InputMessageContent::Text(content),
+    )
+    .description(found.join(", "));
+    if let Some(artwork) = result
+        .metadata
+        .artwork_url
+        .as_deref()
+        .and_then(|url| url.parse().ok())
+    {
+        article = article.thumbnail_url(artwork);
+    }
+    Some(InlineQueryResult::Article(article))
+}
+
+/// Answers `@bot <url or search>` typed in any chat with a result that posts
+/// the links there, so the bot does not need to be a member of the chat.
+pub async fn process_inline_query(
+    bot: &Bot,
+    query: &InlineQuery,
+    db: Arc<DatabaseConnection>,
+    music_service: Arc<MusicLinkService>,
+) -> Result<(), RequestError> {
+    let text = query.query.trim();
+    let mut results = vec![];
+    let mut still_resolving = false;
+    if text.chars().count() >= INLINE_QUERY_MIN_CHARS {
+        let user_country = get_inline_user_country(&query.from, &db)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to look up inline storefront: {}", e);
+                DEFAULT_COUNTRY.to_owned()
+            });
+        match resolve_inline_query(text, user_country, db, music_service).await {
+            Ok(Some(result)) => results.extend(get_inline_result(&result, text)),
+            Ok(None) => still_resolving = true,
+            Err(e) => tracing::debug!("No inline result for {}: {}", text, e),
+        }
+    }
+    let mut answer = bot
+        .answer_inline_query(query.id.clone(), results)
+        .is_personal(true);
+    if still_resolving {
+        // Telegram would otherwise keep serving the empty answer for minutes.
+        answer = answer.cache_time(0);
+    }
+    answer.await?;
+    Ok(())
+}
+
+/// Records a chosen inline result as a share of the user. Inline messages do not
+/// say which chat they were sent to, so the share is kept apart from the chat
+/// shares that /stats and /leaderboard count. Telegram only reports chosen
+/// results once inline feedback is enabled for the bot through BotFather.
+pub async fn process_chosen_inline_result(
+    chosen: &ChosenInlineResult,
+    db: &DatabaseConnection,
+) -> Result<(), DbErr> {
+    let Ok(music_link_id) = Uuid::parse_str(&chosen.result_id) else {
+        tracing::debug!("Ignoring unknown inline result {}", chosen.result_id);
+        return Ok(());
+    };
+    let share = telegram_bot_inline_share::ActiveModel {
+        music_link_id: ActiveValue::Set(music_link_id),
+        telegram_user_id: ActiveValue::Set(chosen.from.id.0 as i64),
+        ..Default::default()
+    };
+    share.insert(db).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn resolve_within_returns_what_finishes_in_time() {
+        let resolved = resolve_within(Duration::from_secs(1), async { 42 }).await;
+        assert_eq!(resolved, Some(42));
+    }
+
+    #[tokio::test]
+    async fn resolve_within_stops_a_timed_out_resolution() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let resolver_calls = calls.clone();
+        let resolving = async move {
+            loop {
+                resolver_calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+
+        let resolved: Option<()> = resolve_within(Duration::from_millis(30), resolving).await;
+        assert_eq!(resolved, None);
+
+        let calls_at_timeout = calls.load(Ordering::SeqCst);
+        assert!(calls_at_timeout > 0);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), calls_at_timeout);
+    }
+}
@@ -1,24 +1,46 @@
 use std::sync::Arc;
 
 use functions::{
-    ProcessMessageResponse, after_process_message, has_url_in_message, is_reply_to_message,
-    process_emoji_reaction, process_music_share, process_text_reaction,
+    CountryScope, ProcessMessageResponse, after_process_message, has_url_in_message,
+    is_reply_to_message, process_country_command, process_emoji_reaction, process_find_command,
+    process_music_share, process_text_reaction,
 };
+use inline::{process_chosen_inline_result, process_inline_query};
 use schematic::{Config, ConfigLoader, validate::not_empty};
 use sea_orm::{Database, DatabaseConnection};
 use sea_orm_migration::MigratorTrait;
 use serde::Serialize;
+use services::{MusicLinkService, MusicLinkSettings, PartialMusicLinkSettings, parse_timezone};
+use settings::{
+    ChatSettings, SETTINGS_CALLBACK_PREFIX, SETTINGS_USAGE, SettingsAction, get_chat_settings,
+    parse_digest_schedule, parse_platform_list, update_chat_settings,
+};
+use stats::{
+    PERIOD_USAGE, StatsPeriod, process_leaderboard_command, process_stats_command,
+    process_top_command, process_wrapped_command,
+};
 use teloxide::{
-    Bot,
-    dispatching::UpdateFilterExt,
-    payloads::{SendMessageSetters, SetMessageReactionSetters},
+    Bot, RequestError,
+    dispatching::{HandlerExt, UpdateFilterExt},
+    payloads::{
+        AnswerCallbackQuerySetters, EditMessageTextSetters, SendMessageSetters,
+        SetMessageReactionSetters,
+    },
     prelude::{Dispatcher, Requester},
     respond,
-    types::{Message, MessageReactionUpdated, ParseMode, ReactionType, Update},
+    types::{
+        CallbackQuery, Chat, ChosenInlineResult, InlineQuery, InputFile, Message,
+        MessageReactionUpdated, ParseMode, ReactionType, ReplyParameters, Update, User,
+    },
+    utils::command::BotCommands,
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod functions;
+mod i18n;
+mod inline;
+mod settings;
+mod stats;
 
 #[derive(Serialize, Config)]
 #[config(env)]
@@ -27,6 +49,279 @@ struct AppConfig {
     database_url: String,
     #[setting(validate = not_empty, env = "TELOXIDE_TOKEN")]
     teloxide_token: String,
+    #[setting(nested)]
+    music_link: MusicLinkSettings,
+}
+
+#[derive(BotCommands, Clone)]
+#[command(
+    rename_rule = "lowercase",
+    description = "These commands are supported:"
+)]
+enum Command {
+    #[command(description = "find a song without a link, e.g. /find artist - title")]
+    Find(String),
+    #[command(
+        description = "set the storefront for your links, e.g. /country GB or /country reset"
+    )]
+    Country(String),
+    #[command(description = "set the default storefront for this chat (admins only)")]
+    ChatCountry(String),
+    #[command(description = "change how the bot behaves in this chat (admins only)")]
+    Settings(String),
+    #[command(description = "show your shares and reactions, e.g. /stats month")]
+    Stats(String),
+    #[command(description = "show the top sharers of this chat, e.g. /leaderboard week")]
+    Leaderboard(String),
+    #[command(description = "show the best received tracks of this chat, e.g. /top year")]
+    Top(String),
+    #[command(description = "show the recap of a year or month, e.g. /wrapped 2025 or /wrapped me")]
+    Wrapped(String),
+}
+
+/// Replies to `/stats`, `/leaderboard` and `/top` over the period given as
+/// their argument.
+async fn process_stats_commands(
+    bot: &Bot,
+    msg: &Message,
+    db: &DatabaseConnection,
+    cmd: Command,
+) -> Result<(), RequestError> {
+    let (Command::Stats(argument) | Command::Leaderboard(argument) | Command::Top(argument)) = &cmd
+    else {
+        return Ok(());
+    };
+    let reply = match StatsPeriod::parse(argument) {
+        None => Ok(PERIOD_USAGE.to_string()),
+        Some(period) => match cmd {
+            Command::Stats(_) => process_stats_command(msg, db, period).await,
+            Command::Leaderboard(_) => process_leaderboard_command(bot, msg, db, period).await,
+            _ => process_top_command(msg, db, period).await,
+        },
+    };
+    match reply {
+        Err(e) => tracing::error!("Failed to process command: {}", e),
+        Ok(reply) => {
+            bot.send_message(msg.chat.id, reply)
+                .parse_mode(ParseMode::Html)
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `user` may change settings for the whole `chat`.
+async fn is_chat_admin(bot: &Bot, chat: &Chat, user: Option<&User>) -> Result<bool, RequestError> {
+    if chat.is_private() {
+        return Ok(true);
+    }
+    let Some(user) = user else {
+        return Ok(false);
+    };
+    let member = bot.get_chat_member(chat.id, user.id).await?;
+    Ok(member.is_privileged())
+}
+
+/// Loads the settings of the chat `msg` was sent in, falling back to the
+/// defaults when they can not be read.
+async fn load_chat_settings(db: &DatabaseConnection, msg: &Message) -> ChatSettings {
+    get_chat_settings(msg.chat.id.0, db)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to load chat settings: {}", e);
+            ChatSettings::default()
+        })
+}
+
+/// Handles `/settings`, `/settings country <code>` and `/settings platforms <list>`.
+async fn process_settings_command(
+    bot: &Bot,
+    msg: &Message,
+    db: &DatabaseConnection,
+    argument: String,
+) -> Result<(), RequestError> {
+    let reply = |text: String| {
+        bot.send_message(msg.chat.id, text)
+            .reply_parameters(ReplyParameters::new(msg.id))
+    };
+    if !is_chat_admin(bot, &msg.chat, msg.from.as_ref()).await? {
+        reply("Only chat admins can change the settings.".to_string()).await?;
+        return Ok(());
+    }
+    let argument = argument.trim();
+    let (name, value) = argument.split_once(' ').unwrap_or((argument, ""));
+    let result = match name {
+        "" => get_chat_settings(msg.chat.id.0, db).await,
+        "country" => {
+            let value = value.to_string();
+            match process_country_command(value, msg, db, CountryScope::Chat).await {
+                Ok(text) => {
+                    reply(text).await?;
+                    return Ok(());
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "platforms" => match parse_platform_list(value) {
+            Ok(platforms) if !platforms.is_empty() => {
+                let action = SettingsAction::SetPlatforms(platforms);
+                update_chat_settings(msg.chat.id.0, action, db).await
+            }
+            Ok(_) => {
+                reply("List at least one platform, e.g. spotify, apple_music.".to_string()).await?;
+                return Ok(());
+            }
+            Err(name) => {
+                reply(format!("{} is not a platform.", name)).await?;
+                return Ok(());
+            }
+        },
+        "digest" => match parse_digest_schedule(value) {
+            Some((weekday, hour)) => {
+                let action = SettingsAction::SetDigestSchedule(weekday, hour);
+                update_chat_settings(msg.chat.id.0, action, db).await
+            }
+            None => {
+                reply("Pick a day and an hour, e.g. /settings digest sun 18.".to_string()).await?;
+                return Ok(());
+            }
+        },
+        "timezone" => match parse_timezone(value) {
+            Some(timezone) => {
+                let action = SettingsAction::SetTimezone(timezone.name().to_string());
+                update_chat_settings(msg.chat.id.0, action, db).await
+            }
+            None => {
+                reply(format!(
+                    "{} is not a timezone, try one like Europe/Berlin.",
+                    value
+                ))
+                .await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            reply(format!("{} is not a setting. {}", name, SETTINGS_USAGE)).await?;
+            return Ok(());
+        }
+    };
+    match result {
+        Err(e) => tracing::error!("Failed to process settings: {}", e),
+        Ok(settings) => {
+            reply(settings.description())
+                .reply_markup(settings.keyboard())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies a tap on the settings keyboard and redraws it.
+async fn process_settings_callback(
+    bot: &Bot,
+    query: &CallbackQuery,
+    db: &DatabaseConnection,
+) -> Result<(), RequestError> {
+    let Some(msg) = query.regular_message() else {
+        return Ok(());
+    };
+    let data = query.data.as_deref().unwrap_or_default();
+    if !is_chat_admin(bot, &msg.chat, Some(&query.from)).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("Only chat admins can change the settings.")
+            .await?;
+        return Ok(());
+    }
+    let Some(action) = SettingsAction::from_callback_data(data) else {
+        bot.answer_callback_query(query.id.clone())
+            .text("Use the commands listed in the settings message to change this.")
+            .await?;
+        return Ok(());
+    };
+    match update_chat_settings(msg.chat.id.0, action, db).await {
+        Err(e) => {
+            tracing::error!("Failed to update settings: {}", e);
+            bot.answer_callback_query(query.id.clone()).await?;
+        }
+        Ok(settings) => {
+            bot.answer_callback_query(query.id.clone()).await?;
+            bot.edit_message_text(msg.chat.id, msg.id, settings.description())
+                .reply_markup(settings.keyboard())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send_process_response(
+    bot: &Bot,
+    msg: &Message,
+    db: &DatabaseConnection,
+    response: ProcessMessageResponse,
+    settings: &ChatSettings,
+) -> Result<(), RequestError> {
+    match response {
+        ProcessMessageResponse::NoUrlDetected => {
+            tracing::debug!("No URL detected in message, ignoring");
+        }
+        ProcessMessageResponse::HasUrlNoMusicLinksFound if !settings.failure_reaction_enabled => {
+            tracing::debug!("URL detected but no music links found, reactions are disabled");
+        }
+        ProcessMessageResponse::HasUrlNoMusicLinksFound => {
+            tracing::debug!("URL detected but no music links found, reacting with sad emoji");
+            bot.set_message_reaction(msg.chat.id, msg.id)
+                .reaction(vec![ReactionType::Emoji {
+                    emoji: settings.failure_reaction_emoji.clone(),
+                }])
+                .await?;
+        }
+        ProcessMessageResponse::HasUrlUpstreamUnavailable => {
+            tracing::debug!("Music services unavailable, asking to retry later");
+            bot.send_message(msg.chat.id, settings.texts().upstream_unavailable)
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+        ProcessMessageResponse::HasUrlAlreadyShared { text } => {
+            tracing::debug!("Rejecting music links that were shared before");
+            bot.send_message(msg.chat.id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .await?;
+        }
+        ProcessMessageResponse::HasUrlMusicLinksFound {
+            text,
+            music_link_ids,
+            reply_to,
+        } => {
+            tracing::info!("Sending music link response to chat {}", msg.chat.id);
+            let request = bot
+                .send_message(msg.chat.id, text)
+                .parse_mode(ParseMode::Html);
+            let sent = if let Some(reply_to) = reply_to {
+                tracing::debug!("Replying to the first share of the music link");
+                let reply = ReplyParameters::new(reply_to).allow_sending_without_reply();
+                let sent = request.reply_parameters(reply).await?;
+                if settings.delete_original {
+                    bot.delete_message(msg.chat.id, msg.id).await?;
+                }
+                sent
+            } else if settings.delete_original {
+                let sent = request.await?;
+                tracing::debug!("Deleting original message");
+                bot.delete_message(msg.chat.id, msg.id).await?;
+                sent
+            } else {
+                request
+                    .reply_parameters(ReplyParameters::new(msg.id))
+                    .await?
+            };
+            after_process_message(db, &sent, music_link_ids, msg)
+                .await
+                .ok();
+        }
+    };
+    Ok(())
 }
 
 #[tokio::main]
@@ -55,49 +350,119 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     migrations::Migrator::up(&db, None).await?;
     tracing::info!("Database migrations completed");
 
+    let db = Arc::new(db);
+    let music_link_service = Arc::new(MusicLinkService::from_config(
+        config.music_link.service_config(),
+    ));
+    let flusher = music_link_service.spawn_interaction_flusher(db.as_ref().clone());
+    tracing::info!("Music link service initialized");
+
     let bot = Bot::new(config.teloxide_token.clone());
 
+    let command_handler = Update::filter_message()
+        .filter_command::<Command>()
+        .endpoint(
+            |bot: Bot,
+             msg: Message,
+             cmd: Command,
+             db: Arc<DatabaseConnection>,
+             music_link_service: Arc<MusicLinkService>| async move {
+                match cmd {
+                    Command::Find(query) if query.trim().is_empty() => {
+                        bot.send_message(msg.chat.id, Command::descriptions().to_string())
+                            .reply_parameters(ReplyParameters::new(msg.id))
+                            .await?;
+                    }
+                    Command::Find(query) => {
+                        let settings = load_chat_settings(&db, &msg).await;
+                        let service = &music_link_service;
+                        match process_find_command(
+                            &bot,
+                            query,
+                            &msg,
+                            db.clone(),
+                            service,
+                            &settings,
+                        )
+                        .await
+                        {
+                            Err(e) => tracing::error!("Failed to process command: {}", e),
+                            Ok(response) => {
+                                send_process_response(&bot, &msg, &db, response, &settings).await?;
+                            }
+                        }
+                    }
+                    Command::Settings(argument) => {
+                        process_settings_command(&bot, &msg, &db, argument).await?;
+                    }
+                    cmd @ (Command::Stats(_) | Command::Leaderboard(_) | Command::Top(_)) => {
+                        process_stats_commands(&bot, &msg, &db, cmd).await?;
+                    }
+                    Command::Wrapped(argument) => {
+                        match process_wrapped_command(&bot, &msg, &db, &argument).await {
+                            Err(e) => tracing::error!("Failed to process command: {}", e),
+                            Ok((text, card)) => {
+                                bot.send_message(msg.chat.id, text)
+                                    .parse_mode(ParseMode::Html)
+                                    .reply_parameters(ReplyParameters::new(msg.id))
+                                    .await?;
+                                if let Some(card) = card {
+                                    let photo = InputFile::memory(card).file_name("wrapped.png");
+                                    bot.send_photo(msg.chat.id, photo).await?;
+                                }
+                            }
+                        }
+                    }
+                    Command::Country(argument) => {
+                        let scope = CountryScope::User;
+                        match process_country_command(argument, &msg, &db, scope).await {
+                            Err(e) => tracing::error!("Failed to process command: {}", e),
+                            Ok(reply) => {
+                                bot.send_message(msg.chat.id, reply)
+                                    .reply_parameters(ReplyParameters::new(msg.id))
+                                    .await?;
+                            }
+                        }
+                    }
+                    Command::ChatCountry(argument) => {
+                        let reply = if !argument.trim().is_empty()
+                            && !is_chat_admin(&bot, &msg.chat, msg.from.as_ref()).await?
+                        {
+                            Ok("Only chat admins can change the chat storefront.".to_string())
+                        } else {
+                            process_country_command(argument, &msg, &db, CountryScope::Chat).await
+                        };
+                        match reply {
+                            Err(e) => tracing::error!("Failed to process command: {}", e),
+                            Ok(reply) => {
+                                bot.send_message(msg.chat.id, reply)
+                                    .reply_parameters(ReplyParameters::new(msg.id))
+                                    .await?;
+                            }
+                        }
+                    }
+                }
+                respond(())
+            },
+        );
+
     let music_share_handler = Update::filter_message()
         .filter(has_url_in_message)
         .endpoint(
-            |bot: Bot, msg: Message, db: Arc<DatabaseConnection>| async move {
-                let chat_id = msg.chat.id;
-                let text = msg.text().unwrap_or_default();
-
-                match process_music_share(text.to_string(), &msg, db.clone()).await {
+            |bot: Bot,
+             msg: Message,
+             db: Arc<DatabaseConnection>,
+             music_link_service: Arc<MusicLinkService>| async move {
+                let settings = load_chat_settings(&db, &msg).await;
+                match process_music_share(&bot, &msg, db.clone(), &music_link_service, &settings)
+                    .await
+                {
                     Err(e) => {
                         tracing::error!("Failed to process message: {}", e);
                     }
-                    Ok(response) => match response {
-                        ProcessMessageResponse::NoUrlDetected => {
-                            tracing::debug!("No URL detected in message, ignoring");
-                        }
-                        ProcessMessageResponse::HasUrlNoMusicLinksFound => {
-                            tracing::debug!(
-                                "URL detected but no music links found, reacting with sad emoji"
-                            );
-                            bot.set_message_reaction(msg.chat.id, msg.id)
-                                .reaction(vec![ReactionType::Emoji {
-                                    emoji: "😢".to_string(),
-                                }])
-                                .await?;
-                        }
-                        ProcessMessageResponse::HasUrlMusicLinksFound {
-                            text,
-                            music_link_ids,
-                        } => {
-                            tracing::info!("Sending music link response to chat {}", chat_id);
-                            let sent = bot
-                                .send_message(msg.chat.id, text)
-                                .parse_mode(ParseMode::Html)
-                                .await?;
-                            tracing::debug!("Deleting original message");
-                            bot.delete_message(msg.chat.id, msg.id).await?;
-                            after_process_message(&db, &sent, music_link_ids, &msg)
-                                .await
-                                .ok();
-                        }
-                    },
+                    Ok(response) => {
+                        send_process_response(&bot, &msg, &db, response, &settings).await?
+                    }
                 };
 
                 respond(())
@@ -118,20 +483,62 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         },
     );
 
+    let settings_callback_handler = Update::filter_callback_query()
+        .filter(|query: CallbackQuery| {
+            query
+                .data
+                .as_deref()
+                .is_some_and(|data| data.starts_with(SETTINGS_CALLBACK_PREFIX))
+        })
+        .endpoint(
+            |bot: Bot, query: CallbackQuery, db: Arc<DatabaseConnection>| async move {
+                process_settings_callback(&bot, &query, &db).await?;
+                respond(())
+            },
+        );
+
+    let inline_query_handler = Update::filter_inline_query().endpoint(
+        |bot: Bot,
+         query: InlineQuery,
+         db: Arc<DatabaseConnection>,
+         music_link_service: Arc<MusicLinkService>| async move {
+            process_inline_query(&bot, &query, db, music_link_service).await?;
+            respond(())
+        },
+    );
+
+    let chosen_inline_result_handler = Update::filter_chosen_inline_result().endpoint(
+        |chosen: ChosenInlineResult, db: Arc<DatabaseConnection>| async move {
+            if let Err(e) = process_chosen_inline_result(&chosen, &db).await {
+                tracing::error!("Failed to record inline share: {}", e);
+            }
+            respond(())
+        },
+    );
+
     tracing::info!("Starting Telegram bot dispatcher");
 
     let handler = dptree::entry()
+        .branch(command_handler)
         .branch(music_share_handler)
         .branch(text_reaction_handler)
-        .branch(emoji_reaction_handler);
+        .branch(emoji_reaction_handler)
+        .branch(settings_callback_handler)
+        .branch(inline_query_handler)
+        .branch(chosen_inline_result_handler);
 
     Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![Arc::new(db)])
+        .dependencies(dptree::deps![db.clone(), music_link_service.clone()])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
         .await;
 
+    flusher.abort();
+    if let Err(e) = music_link_service.flush_interactions(&db).await {
+        tracing::warn!("Failed to flush music link interactions: {}", e);
+    }
+
     tracing::info!("Telegram bot shutdown complete");
     Ok(())
 }
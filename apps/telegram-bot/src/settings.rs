@@ -0,0 +1,535 @@
+use chrono::Weekday;
+use convert_case::{Case, Casing};
+use entities::{
+    prelude::{TelegramBotChannel, TelegramBotChannelSettings},
+    telegram_bot_channel,
+    telegram_bot_channel_settings::{self, DuplicateShareMode, ReplyLanguage},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, Iterable,
+    ModelTrait, QueryFilter,
+};
+use services::MusicPlatform;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::{
+    functions::find_or_create_telegram_channel,
+    i18n::{Texts, language_name, texts},
+};
+
+/// Prefix of the callback data sent by the settings keyboard.
+pub static SETTINGS_CALLBACK_PREFIX: &str = "settings:";
+
+/// Reactions the failure emoji cycles through, since Telegram only accepts a
+/// fixed set of emojis as reactions.
+static FAILURE_REACTION_EMOJIS: [&str; 5] = ["😢", "🤷", "👎", "🤔", "💔"];
+
+/// How to change the settings that have no button of their own.
+pub static SETTINGS_USAGE: &str = "Use /settings country GB to change the storefront, \
+     /settings platforms spotify, apple_music to pick platforms and their order, \
+     /settings digest sun 18 to schedule the weekly digest and \
+     /settings timezone Europe/Berlin to set the timezone it is scheduled in.";
+
+#[derive(Debug, Clone)]
+pub struct ChatSettings {
+    pub delete_original: bool,
+    pub failure_reaction_enabled: bool,
+    pub failure_reaction_emoji: String,
+    /// Platforms shown in replies, in the order they are shown.
+    pub platforms: Vec<MusicPlatform>,
+    /// Language of the replies to shared links. Command replies such as
+    /// /stats and /wrapped are always in English.
+    pub reply_language: ReplyLanguage,
+    pub duplicate_share_mode: DuplicateShareMode,
+    pub country: Option<String>,
+    pub digest_enabled: bool,
+    pub digest_weekday: Weekday,
+    pub digest_hour: u32,
+    /// IANA name of the timezone the digest is scheduled and recaps are counted in.
+    pub timezone: String,
+    pub wrapped_enabled: bool,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            delete_original: true,
+            failure_reaction_enabled: true,
+            failure_reaction_emoji: FAILURE_REACTION_EMOJIS[0].to_string(),
+            platforms: MusicPlatform::iter().collect(),
+            reply_language: ReplyLanguage::default(),
+            duplicate_share_mode: DuplicateShareMode::default(),
+            country: None,
+            digest_enabled: false,
+            digest_weekday: Weekday::Sun,
+            digest_hour: 18,
+            timezone: "UTC".to_string(),
+            wrapped_enabled: false,
+        }
+    }
+}
+
+/// No stored platforms means every platform in the default order.
+fn parse_platforms(stored: &[String]) -> Vec<MusicPlatform> {
+    if stored.is_empty() {
+        return MusicPlatform::iter().collect();
+    }
+    stored
+        .iter()
+        .filter_map(|platform| platform.parse().ok())
+        .collect()
+}
+
+/// Reads a weekday stored from 1 for Monday to 7 for Sunday.
+fn parse_weekday(stored: i16) -> Option<Weekday> {
+    let days_from_monday = u8::try_from(stored).ok()?.checked_sub(1)?;
+    Weekday::try_from(days_from_monday).ok()
+}
+
+pub fn platform_name(platform: MusicPlatform) -> String {
+    format!("{:?}", platform).to_case(Case::Title)
+}
+
+impl ChatSettings {
+    fn new(
+        channel: &telegram_bot_channel::Model,
+        settings: Option<telegram_bot_channel_settings::Model>,
+    ) -> Self {
+        let defaults = Self {
+            country: channel.country.clone(),
+            ..Default::default()
+        };
+        let Some(settings) = settings else {
+            return defaults;
+        };
+        Self {
+            delete_original: settings.delete_original,
+            failure_reaction_enabled: settings.failure_reaction_enabled,
+            failure_reaction_emoji: settings.failure_reaction_emoji,
+            platforms: parse_platforms(&settings.platforms),
+            reply_language: settings.reply_language,
+            duplicate_share_mode: settings.duplicate_share_mode,
+            digest_enabled: settings.digest_enabled,
+            digest_weekday: parse_weekday(settings.digest_weekday)
+                .unwrap_or(defaults.digest_weekday),
+            digest_hour: u32::try_from(settings.digest_hour).unwrap_or(defaults.digest_hour),
+            timezone: settings.timezone,
+            wrapped_enabled: settings.wrapped_enabled,
+            ..defaults
+        }
+    }
+
+    pub fn texts(&self) -> &'static Texts {
+        texts(self.reply_language)
+    }
+
+    fn apply(&mut self, action: SettingsAction) {
+        match action {
+            SettingsAction::ToggleDeleteOriginal => self.delete_original = !self.delete_original,
+            SettingsAction::ToggleFailureReaction => {
+                self.failure_reaction_enabled = !self.failure_reaction_enabled;
+            }
+            SettingsAction::NextFailureReactionEmoji => {
+                let current = FAILURE_REACTION_EMOJIS
+                    .iter()
+                    .position(|emoji| *emoji == self.failure_reaction_emoji);
+                let next = current.map_or(0, |idx| (idx + 1) % FAILURE_REACTION_EMOJIS.len());
+                self.failure_reaction_emoji = FAILURE_REACTION_EMOJIS[next].to_string();
+            }
+            SettingsAction::NextReplyLanguage => {
+                let languages: Vec<_> = ReplyLanguage::iter().collect();
+                let current = languages
+                    .iter()
+                    .position(|language| *language == self.reply_language)
+                    .unwrap_or_default();
+                self.reply_language = languages[(current + 1) % languages.len()];
+            }
+            SettingsAction::NextDuplicateShareMode => {
+                let modes: Vec<_> = DuplicateShareMode::iter().collect();
+                let current = modes
+                    .iter()
+                    .position(|mode| *mode == self.duplicate_share_mode)
+                    .unwrap_or_default();
+                self.duplicate_share_mode = modes[(current + 1) % modes.len()];
+            }
+            SettingsAction::TogglePlatform(platform) => {
+                if !self.platforms.contains(&platform) {
+                    self.platforms.push(platform);
+                } else if self.platforms.len() > 1 {
+                    self.platforms.retain(|shown| *shown != platform);
+                }
+            }
+            SettingsAction::MovePlatformUp(platform) => {
+                if let Some(idx) = self.platforms.iter().position(|shown| *shown == platform)
+                    && idx > 0
+                {
+                    self.platforms.swap(idx - 1, idx);
+                }
+            }
+            SettingsAction::SetPlatforms(platforms) => {
+                if !platforms.is_empty() {
+                    self.platforms = platforms;
+                }
+            }
+            SettingsAction::ToggleDigest => self.digest_enabled = !self.digest_enabled,
+            SettingsAction::SetDigestSchedule(weekday, hour) => {
+                self.digest_weekday = weekday;
+                self.digest_hour = hour.min(23);
+            }
+            SettingsAction::SetTimezone(timezone) => self.timezone = timezone,
+            SettingsAction::ToggleWrapped => self.wrapped_enabled = !self.wrapped_enabled,
+        }
+    }
+
+    /// Platforms to store, leaving the list empty while it matches the default.
+    fn stored_platforms(&self) -> Vec<String> {
+        if self.platforms.iter().copied().eq(MusicPlatform::iter()) {
+            return vec![];
+        }
+        self.platforms
+            .iter()
+            .map(|platform| platform.as_ref().to_owned())
+            .collect()
+    }
+
+    pub fn description(&self) -> String {
+        let platforms: Vec<_> = self
+            .platforms
+            .iter()
+            .map(|platform| platform_name(*platform))
+            .collect();
+        format!(
+            "Settings for this chat. Tap a button to change one.\n\n\
+             Platforms shown: {}\n\n{}",
+            platforms.join(", "),
+            SETTINGS_USAGE
+        )
+    }
+
+    pub fn keyboard(&self) -> InlineKeyboardMarkup {
+        // Buttons for settings that are only set by command answer with how
+        // to use the command, like the storefront and schedule buttons.
+        let button = |text: String, action: SettingsAction| {
+            let data = action
+                .callback_data()
+                .unwrap_or_else(|| format!("{SETTINGS_CALLBACK_PREFIX}command"));
+            InlineKeyboardButton::callback(text, data)
+        };
+        let original = if self.delete_original {
+            "Original message: delete"
+        } else {
+            "Original message: reply to it"
+        };
+        let reaction = if self.failure_reaction_enabled {
+            "Failure reaction: on"
+        } else {
+            "Failure reaction: off"
+        };
+        let duplicates = match self.duplicate_share_mode {
+            DuplicateShareMode::Annotate => "Duplicates: mention first share",
+            DuplicateShareMode::ReplyToOriginal => "Duplicates: reply to first share",
+            DuplicateShareMode::Reject => "Duplicates: reject",
+        };
+        let digest = if self.digest_enabled {
+            "Weekly digest: on"
+        } else {
+            "Weekly digest: off"
+        };
+        let wrapped = if self.wrapped_enabled {
+            "Wrapped recaps: on"
+        } else {
+            "Wrapped recaps: off"
+        };
+        let schedule = format!(
+            "{} {:02}:00 {}",
+            self.digest_weekday, self.digest_hour, self.timezone
+        );
+        let country = format!(
+            "Storefront: {}",
+            self.country.as_deref().unwrap_or("automatic")
+        );
+        let mut keyboard = InlineKeyboardMarkup::new([
+            vec![button(
+                original.to_string(),
+                SettingsAction::ToggleDeleteOriginal,
+            )],
+            vec![
+                button(reaction.to_string(), SettingsAction::ToggleFailureReaction),
+                button(
+                    format!("Emoji: {}", self.failure_reaction_emoji),
+                    SettingsAction::NextFailureReactionEmoji,
+                ),
+            ],
+            vec![button(
+                duplicates.to_string(),
+                SettingsAction::NextDuplicateShareMode,
+            )],
+            vec![button(
+                format!(
+                    "Link reply language: {}",
+                    language_name(self.reply_language)
+                ),
+                SettingsAction::NextReplyLanguage,
+            )],
+            vec![InlineKeyboardButton::callback(
+                country,
+                format!("{SETTINGS_CALLBACK_PREFIX}country"),
+            )],
+            vec![
+                button(digest.to_string(), SettingsAction::ToggleDigest),
+                InlineKeyboardButton::callback(
+                    schedule,
+                    format!("{SETTINGS_CALLBACK_PREFIX}schedule"),
+                ),
+            ],
+            vec![button(wrapped.to_string(), SettingsAction::ToggleWrapped)],
+        ]);
+        for (idx, platform) in self.platforms.iter().enumerate() {
+            let mut row = vec![button(
+                format!("✅ {}", platform_name(*platform)),
+                SettingsAction::TogglePlatform(*platform),
+            )];
+            if idx > 0 {
+                row.push(button(
+                    "⬆️".to_string(),
+                    SettingsAction::MovePlatformUp(*platform),
+                ));
+            }
+            keyboard = keyboard.append_row(row);
+        }
+        for platform in MusicPlatform::iter().filter(|p| !self.platforms.contains(p)) {
+            keyboard = keyboard.append_row([button(
+                format!("❌ {}", platform_name(platform)),
+                SettingsAction::TogglePlatform(platform),
+            )]);
+        }
+        keyboard
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsAction {
+    ToggleDeleteOriginal,
+    ToggleFailureReaction,
+    NextFailureReactionEmoji,
+    NextReplyLanguage,
+    NextDuplicateShareMode,
+    TogglePlatform(MusicPlatform),
+    MovePlatformUp(MusicPlatform),
+    SetPlatforms(Vec<MusicPlatform>),
+    ToggleDigest,
+    SetDigestSchedule(Weekday, u32),
+    SetTimezone(String),
+    ToggleWrapped,
+}
+
+impl SettingsAction {
+    /// Reads an action from the callback data of a settings keyboard button.
+    pub fn from_callback_data(data: &str) -> Option<Self> {
+        let action = data.strip_prefix(SETTINGS_CALLBACK_PREFIX)?;
+        let action = match action.split_once(':') {
+            None => match action {
+                "delete" => Self::ToggleDeleteOriginal,
+                "reaction" => Self::ToggleFailureReaction,
+                "emoji" => Self::NextFailureReactionEmoji,
+                "language" => Self::NextReplyLanguage,
+                "duplicates" => Self::NextDuplicateShareMode,
+                "digest" => Self::ToggleDigest,
+                "wrapped" => Self::ToggleWrapped,
+                _ => return None,
+            },
+            Some(("platform", platform)) => Self::TogglePlatform(platform.parse().ok()?),
+            Some(("up", platform)) => Self::MovePlatformUp(platform.parse().ok()?),
+            Some(_) => return None,
+        };
+        Some(action)
+    }
+
+    /// The callback data for a keyboard button, or `None` for actions that
+    /// carry a value and can only be set by command.
+    fn callback_data(&self) -> Option<String> {
+        let action = match self {
+            Self::ToggleDeleteOriginal => "delete".to_string(),
+            Self::ToggleFailureReaction => "reaction".to_string(),
+            Self::NextFailureReactionEmoji => "emoji".to_string(),
+            Self::NextReplyLanguage => "language".to_string(),
+            Self::NextDuplicateShareMode => "duplicates".to_string(),
+            Self::ToggleDigest => "digest".to_string(),
+            Self::ToggleWrapped => "wrapped".to_string(),
+            Self::TogglePlatform(platform) => format!("platform:{}", platform.as_ref()),
+            Self::MovePlatformUp(platform) => format!("up:{}", platform.as_ref()),
+            Self::SetPlatforms(_) | Self::SetDigestSchedule(..) | Self::SetTimezone(_) => {
+                return None;
+            }
+        };
+        Some(format!("{SETTINGS_CALLBACK_PREFIX}{action}"))
+    }
+}
+
+/// Reads a list such as `spotify, apple_music` into platforms, failing on the
+/// first name that is not a platform.
+pub fn parse_platform_list(list: &str) -> Result<Vec<MusicPlatform>, String> {
+    let mut platforms = vec![];
+    for name in list.split([',', ' ']).filter(|name| !name.is_empty()) {
+        let name = name.trim().to_lowercase();
+        let platform: MusicPlatform = name.parse().map_err(|_| name.clone())?;
+        if !platforms.contains(&platform) {
+            platforms.push(platform);
+        }
+    }
+    Ok(platforms)
+}
+
+/// Reads a digest schedule such as `sun 18` into a weekday and an hour.
+pub fn parse_digest_schedule(schedule: &str) -> Option<(Weekday, u32)> {
+    let (weekday, hour) = schedule.trim().split_once(' ')?;
+    let weekday = weekday.parse().ok()?;
+    let hour = hour.trim().parse().ok().filter(|hour| *hour < 24)?;
+    Some((weekday, hour))
+}
+
+pub async fn get_chat_settings(
+    telegram_channel_id: i64,
+    db: &DatabaseConnection,
+) -> Result<ChatSettings, DbErr> {
+    let channel = TelegramBotChannel::find()
+        .filter(telegram_bot_channel::Column::TelegramChannelId.eq(telegram_channel_id))
+        .one(db)
+        .await?;
+    let Some(channel) = channel else {
+        return Ok(ChatSettings::default());
+    };
+    let settings = channel
+        .find_related(TelegramBotChannelSettings)
+        .one(db)
+        .await?;
+    Ok(ChatSettings::new(&channel, settings))
+}
+
+pub async fn update_chat_settings(
+    telegram_channel_id: i64,
+    action: SettingsAction,
+    db: &DatabaseConnection,
+) -> Result<ChatSettings, DbErr> {
+    let channel = find_or_create_telegram_channel(telegram_channel_id, db).await?;
+    let existing = channel
+        .find_related(TelegramBotChannelSettings)
+        .one(db)
+        .await?;
+    let mut settings = ChatSettings::new(&channel, existing.clone());
+    settings.apply(action);
+    let mut model: telegram_bot_channel_settings::ActiveModel = match existing {
+        Some(existing) => existing.into(),
+        None => telegram_bot_channel_settings::ActiveModel {
+            telegram_bot_channel_id: ActiveValue::Set(channel.id),
+            ..Default::default()
+        },
+    };
+    model.delete_original = ActiveValue::Set(settings.delete_original);
+    model.failure_reaction_enabled = ActiveValue::Set(settings.failure_reaction_enabled);
+    model.failure_reaction_emoji = ActiveValue::Set(settings.failure_reaction_emoji.clone());
+    model.platforms = ActiveValue::Set(settings.stored_platforms());
+    model.reply_language = ActiveValue::Set(settings.reply_language);
+    model.duplicate_share_mode = ActiveValue::Set(settings.duplicate_share_mode);
+    model.digest_enabled = ActiveValue::Set(settings.digest_enabled);
+    model.digest_weekday = ActiveValue::Set(settings.digest_weekday.number_from_monday() as i16);
+    model.digest_hour = ActiveValue::Set(settings.digest_hour as i16);
+    model.timezone = ActiveValue::Set(settings.timezone.clone());
+    model.wrapped_enabled = ActiveValue::Set(settings.wrapped_enabled);
+    model.save(db).await?;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_actions_round_trip_through_callback_data() {
+        let mut actions = vec![
+            SettingsAction::ToggleDeleteOriginal,
+            SettingsAction::ToggleFailureReaction,
+            SettingsAction::NextFailureReactionEmoji,
+            SettingsAction::NextReplyLanguage,
+            SettingsAction::NextDuplicateShareMode,
+            SettingsAction::ToggleDigest,
+            SettingsAction::ToggleWrapped,
+        ];
+        for platform in MusicPlatform::iter() {
+            actions.push(SettingsAction::TogglePlatform(platform));
+            actions.push(SettingsAction::MovePlatformUp(platform));
+        }
+        for action in actions {
+            let data = action.callback_data().unwrap();
+            // Telegram rejects callback data longer than 64 bytes.
+            assert!(data.len() <= 64, "{data}");
+            assert_eq!(SettingsAction::from_callback_data(&data), Some(action));
+        }
+    }
+
+    #[test]
+    fn command_only_actions_have_no_callback_data() {
+        for action in [
+            SettingsAction::SetPlatforms(vec![MusicPlatform::Spotify]),
+            SettingsAction::SetDigestSchedule(Weekday::Sun, 18),
+            SettingsAction::SetTimezone("UTC".to_string()),
+        ] {
+            assert_eq!(action.callback_data(), None);
+        }
+    }
+
+    #[test]
+    fn unknown_callback_data_is_rejected() {
+        for data in [
+            "delete",
+            "other:delete",
+            "settings:",
+            "settings:command",
+            "settings:country",
+            "settings:schedule",
+            "settings:platform:myspace",
+            "settings:up:",
+            "settings:platform:spotify:extra",
+            "settings:delete:extra",
+        ] {
+            assert_eq!(SettingsAction::from_callback_data(data), None, "{data}");
+        }
+    }
+
+    #[test]
+    fn platform_lists_keep_order_and_drop_repeats() {
+        assert_eq!(
+            parse_platform_list("spotify, apple_music"),
+            Ok(vec![MusicPlatform::Spotify, MusicPlatform::AppleMusic])
+        );
+        assert_eq!(
+            parse_platform_list(" Deezer,spotify  deezer "),
+            Ok(vec![MusicPlatform::Deezer, MusicPlatform::Spotify])
+        );
+        assert_eq!(parse_platform_list(" , "), Ok(vec![]));
+    }
+
+    #[test]
+    fn platform_lists_name_the_first_unknown_platform() {
+        assert_eq!(
+            parse_platform_list("spotify, MySpace, napster2"),
+            Err("myspace".to_string())
+        );
+    }
+
+    #[test]
+    fn digest_schedules_read_a_weekday_and_an_hour() {
+        assert_eq!(parse_digest_schedule("sun 18"), Some((Weekday::Sun, 18)));
+        assert_eq!(
+            parse_digest_schedule(" Monday  0 "),
+            Some((Weekday::Mon, 0))
+        );
+    }
+
+    #[test]
+    fn invalid_digest_schedules_are_rejected() {
+        for schedule in ["", "sun", "18", "sun 24", "sun -1", "someday 18", "sun 6pm"] {
+            assert_eq!(parse_digest_schedule(schedule), None, "{schedule}");
+        }
+    }
+}
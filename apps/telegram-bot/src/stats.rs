@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Duration, Utc};
+use sea_orm::DatabaseConnection;
+use services::{
+    Result, ShareCounts, WrappedPeriod, best_received_sharers, format_wrapped, parse_timezone,
+    render_wrapped_card, top_sharers, top_tracks, user_share_stats, wrapped_recap,
+};
+use teloxide::{
+    Bot,
+    types::{ChatId, Message},
+    utils::html::{bold, escape},
+};
+
+use crate::{functions::find_chat_user, settings::get_chat_settings};
+
+/// Number of entries in each leaderboard.
+const LEADERBOARD_SIZE: u64 = 5;
+/// Number of tracks listed by `/top`.
+const TOP_TRACKS_SIZE: u64 = 10;
+/// Sharers need this many rated reactions to rank by how well they were received.
+const MIN_RATED_REACTIONS: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsPeriod {
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl StatsPeriod {
+    /// Reads a period such as `week` from a command argument, where nothing
+    /// means all time.
+    pub fn parse(argument: &str) -> Option<Self> {
+        let period = match argument.trim().to_lowercase().as_str() {
+            "" | "all" => Self::All,
+            "week" => Self::Week,
+            "month" => Self::Month,
+            "year" => Self::Year,
+            _ => return None,
+        };
+        Some(period)
+    }
+
+    fn since(self) -> Option<DateTime<Utc>> {
+        let days = match self {
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::Year => 365,
+            Self::All => return None,
+        };
+        Some(Utc::now() - Duration::days(days))
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Week => "last 7 days",
+            Self::Month => "last 30 days",
+            Self::Year => "last 365 days",
+            Self::All => "all time",
+        }
+    }
+}
+
+pub static PERIOD_USAGE: &str = "Pick a period out of week, month, year or all.";
+
+async fn user_name(bot: &Bot, chat_id: ChatId, telegram_user_id: i64) -> String {
+    match find_chat_user(bot, chat_id, telegram_user_id).await {
+        Some(user) => escape(&user.full_name()),
+        None => "Someone who left".to_string(),
+    }
+}
+
+fn format_ratio(counts: &ShareCounts) -> String {
+    format!(
+        "{:.0}% positive of {} rated",
+        counts.positive_ratio() * 100.0,
+        counts.rated_reactions
+    )
+}
+
+/// Shares and reactions of the sender of `msg`, or of the user they replied to.
+pub async fn process_stats_command(
+    msg: &Message,
+    db: &DatabaseConnection,
+    period: StatsPeriod,
+) -> Result<String> {
+    let user = msg
+        .reply_to_message()
+        .and_then(|reply| reply.from.as_ref())
+        .or(msg.from.as_ref());
+    let Some(user) = user else {
+        return Ok("Only users have stats.".to_string());
+    };
+    let counts = user_share_stats(msg.chat.id.0, user.id.0 as i64, period.since(), db).await?;
+    let heading = format!(
+        "{} ({})",
+        bold(&format!("Stats for {}", escape(&user.full_name()))),
+        period.label()
+    );
+    if counts.shares == 0 {
+        return Ok(format!("{}\nNo shares yet.", heading));
+    }
+    Ok(format!(
+        "{}\nShares: {}\nReactions received: {}\nSentiment: {}",
+        heading,
+        counts.shares,
+        counts.reactions,
+        format_ratio(&counts)
+    ))
+}
+
+/// Top sharers and best received sharers of the chat `msg` was sent in.
+pub async fn process_leaderboard_command(
+    bot: &Bot,
+    msg: &Message,
+    db: &DatabaseConnection,
+    period: StatsPeriod,
+) -> Result<String> {
+    let chat_id = msg.chat.id;
+    let since = period.since();
+    let sharers = top_sharers(chat_id.0, since, LEADERBOARD_SIZE, db).await?;
+    let best_received =
+        best_received_sharers(chat_id.0, since, MIN_RATED_REACTIONS, LEADERBOARD_SIZE, db).await?;
+    let mut response = format!("{} ({})", bold("Leaderboard"), period.label());
+    if sharers.is_empty() {
+        response.push_str("\nNo shares yet.");
+        return Ok(response);
+    }
+    response.push_str(&format!("\n\n{}", bold("Top sharers")));
+    for (idx, sharer) in sharers.iter().enumerate() {
+        let name = user_name(bot, chat_id, sharer.telegram_user_id).await;
+        response.push_str(&format!(
+            "\n{}. {} – {} shares",
+            idx + 1,
+            name,
+            sharer.counts.shares
+        ));
+    }
+    if !best_received.is_empty() {
+        response.push_str(&format!("\n\n{}", bold("Best received")));
+        for (idx, sharer) in best_received.iter().enumerate() {
+            let name = user_name(bot, chat_id, sharer.telegram_user_id).await;
+            response.push_str(&format!(
+                "\n{}. {} – {}",
+                idx + 1,
+                name,
+                format_ratio(&sharer.counts)
+            ));
+        }
+    }
+    Ok(response)
+}
+
+/// The most positively received tracks of the chat `msg` was sent in.
+pub async fn process_top_command(
+    msg: &Message,
+    db: &DatabaseConnection,
+    period: StatsPeriod,
+) -> Result<String> {
+    let tracks = top_tracks(msg.chat.id.0, period.since(), TOP_TRACKS_SIZE, db).await?;
+    let mut response = format!("{} ({})", bold("Top tracks"), period.label());
+    if tracks.is_empty() {
+        response.push_str("\nNo positive reactions yet.");
+        return Ok(response);
+    }
+    for (idx, track) in tracks.iter().enumerate() {
+        let title = track.title.as_deref().unwrap_or("Unknown title");
+        let heading = match track.artists.is_empty() {
+            true => title.to_string(),
+            false => format!("{} – {}", track.artists.join(", "), title),
+        };
+        response.push_str(&format!(
+            "\n{}. {} – {} positive reactions",
+            idx + 1,
+            escape(&heading),
+            track.counts.positive_reactions
+        ));
+    }
+    Ok(response)
+}
+
+pub static WRAPPED_USAGE: &str =
+    "Pick a year or a month, e.g. /wrapped 2025 or /wrapped 2025-03, and add me for your own.";
+
+/// The recap of the chat `msg` was sent in, or of one user when the argument
+/// says `me` or `msg` replies to them, along with its card.
+pub async fn process_wrapped_command(
+    bot: &Bot,
+    msg: &Message,
+    db: &DatabaseConnection,
+    argument: &str,
+) -> Result<(String, Option<Vec<u8>>)> {
+    let settings = get_chat_settings(msg.chat.id.0, db).await?;
+    let timezone = parse_timezone(&settings.timezone).unwrap_or_default();
+    let mut period = WrappedPeriod::Year(Utc::now().with_timezone(&timezone).year());
+    let mut user = msg.reply_to_message().and_then(|reply| reply.from.as_ref());
+    for token in argument.split_whitespace() {
+        match token.to_lowercase().as_str() {
+            "me" => user = msg.from.as_ref(),
+            token => match WrappedPeriod::parse(token) {
+                Some(parsed) => period = parsed,
+                None => return Ok((WRAPPED_USAGE.to_string(), None)),
+            },
+        }
+    }
+    let user_id = user.map(|user| user.id.0 as i64);
+    let recap = wrapped_recap(msg.chat.id.0, user_id, period, timezone, db).await?;
+    let mut names = HashMap::new();
+    for id in recap.telegram_user_ids() {
+        if let Some(user) = find_chat_user(bot, msg.chat.id, id).await {
+            names.insert(id, user.full_name());
+        }
+    }
+    let card = match recap.counts.shares {
+        0 => None,
+        _ => render_wrapped_card(&recap, &names),
+    };
+    Ok((format_wrapped(&recap, &names), card))
+}
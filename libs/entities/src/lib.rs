@@ -3,7 +3,11 @@
 pub mod prelude;
 
 pub mod music_link;
+pub mod music_link_negative_cache;
+pub mod music_link_platform;
 pub mod telegram_bot_channel;
+pub mod telegram_bot_channel_settings;
+pub mod telegram_bot_inline_share;
 pub mod telegram_bot_music_share;
 pub mod telegram_bot_music_share_reaction;
 pub mod telegram_bot_user;
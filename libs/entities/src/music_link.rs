@@ -1,28 +1,79 @@
-//! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
-
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
 use uuid::Uuid;
 
+#[derive(
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    EnumIter,
+    EnumString,
+    AsRefStr,
+    DeriveActiveEnum,
+)]
+#[sea_orm(
+    rs_type = "String",
+    rename_all = "snake_case",
+    db_type = "String(StringLen::None)"
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum MusicEntityKind {
+    #[default]
+    Song,
+    Album,
+    Artist,
+    Playlist,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "music_link")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
-    pub all_links: Vec<String>,
+    pub kind: MusicEntityKind,
+    pub upc: Option<String>,
+    pub isrc: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub country: String,
     pub created_at: DateTimeUtc,
-    pub spotify_link: Option<String>,
+    pub artwork_url: Option<String>,
+    pub duration_ms: Option<i32>,
     pub equivalent_links: Vec<String>,
     pub last_interacted_at: DateTimeUtc,
-    pub apple_music_link: Option<String>,
-    pub youtube_music_link: Option<String>,
+    pub last_resolved_at: DateTimeUtc,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::music_link_platform::Entity")]
+    MusicLinkPlatform,
+    #[sea_orm(has_many = "super::telegram_bot_inline_share::Entity")]
+    TelegramBotInlineShare,
     #[sea_orm(has_many = "super::telegram_bot_music_share::Entity")]
     TelegramBotMusicShare,
 }
 
+impl Related<super::music_link_platform::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MusicLinkPlatform.def()
+    }
+}
+
+impl Related<super::telegram_bot_inline_share::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TelegramBotInlineShare.def()
+    }
+}
+
 impl Related<super::telegram_bot_music_share::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::TelegramBotMusicShare.def()
@@ -0,0 +1,82 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    EnumIter,
+    EnumString,
+    AsRefStr,
+    DeriveActiveEnum,
+)]
+#[sea_orm(
+    rs_type = "String",
+    rename_all = "snake_case",
+    db_type = "String(StringLen::None)"
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum MusicPlatform {
+    Spotify,
+    AppleMusic,
+    YoutubeMusic,
+    Youtube,
+    Deezer,
+    Tidal,
+    Soundcloud,
+    AmazonMusic,
+    AmazonStore,
+    Bandcamp,
+    Pandora,
+    Napster,
+    Audiomack,
+    Anghami,
+    Boomplay,
+    Audius,
+    Yandex,
+    Spinrilla,
+    Itunes,
+    Google,
+    GoogleStore,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "music_link_platform")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub link: String,
+    pub platform: MusicPlatform,
+    pub created_at: DateTimeUtc,
+    pub confidence: i16,
+    pub is_available: bool,
+    pub availability_checked_at: Option<DateTimeUtc>,
+    pub music_link_id: Uuid,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::music_link::Entity",
+        from = "Column::MusicLinkId",
+        to = "super::music_link::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    MusicLink,
+}
+
+impl Related<super::music_link::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MusicLink.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,7 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
 
 pub use super::music_link::Entity as MusicLink;
+pub use super::music_link_negative_cache::Entity as MusicLinkNegativeCache;
+pub use super::music_link_platform::Entity as MusicLinkPlatform;
 pub use super::telegram_bot_channel::Entity as TelegramBotChannel;
+pub use super::telegram_bot_channel_settings::Entity as TelegramBotChannelSettings;
+pub use super::telegram_bot_inline_share::Entity as TelegramBotInlineShare;
 pub use super::telegram_bot_music_share::Entity as TelegramBotMusicShare;
 pub use super::telegram_bot_music_share_reaction::Entity as TelegramBotMusicShareReaction;
 pub use super::telegram_bot_user::Entity as TelegramBotUser;
@@ -9,16 +9,25 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
     pub created_at: DateTimeUtc,
+    pub country: Option<String>,
     pub telegram_channel_id: i64,
     pub last_interacted_at: DateTimeUtc,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_one = "super::telegram_bot_channel_settings::Entity")]
+    TelegramBotChannelSettings,
     #[sea_orm(has_many = "super::telegram_bot_user::Entity")]
     TelegramBotUser,
 }
 
+impl Related<super::telegram_bot_channel_settings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TelegramBotChannelSettings.def()
+    }
+}
+
 impl Related<super::telegram_bot_user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::TelegramBotUser.def()
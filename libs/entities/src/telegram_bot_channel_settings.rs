@@ -0,0 +1,71 @@
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(
+    rs_type = "String",
+    rename_all = "lowercase",
+    db_type = "String(StringLen::None)"
+)]
+pub enum ReplyLanguage {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+    Pt,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(
+    rs_type = "String",
+    rename_all = "snake_case",
+    db_type = "String(StringLen::None)"
+)]
+pub enum DuplicateShareMode {
+    #[default]
+    Annotate,
+    ReplyToOriginal,
+    Reject,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "telegram_bot_channel_settings")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub created_at: DateTimeUtc,
+    pub telegram_bot_channel_id: Uuid,
+    pub delete_original: bool,
+    pub failure_reaction_enabled: bool,
+    pub failure_reaction_emoji: String,
+    pub platforms: Vec<String>,
+    pub reply_language: ReplyLanguage,
+    pub duplicate_share_mode: DuplicateShareMode,
+    pub digest_enabled: bool,
+    pub digest_weekday: i16,
+    pub digest_hour: i16,
+    pub timezone: String,
+    pub digest_last_sent_at: Option<DateTimeUtc>,
+    pub wrapped_enabled: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::telegram_bot_channel::Entity",
+        from = "Column::TelegramBotChannelId",
+        to = "super::telegram_bot_channel::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    TelegramBotChannel,
+}
+
+impl Related<super::telegram_bot_channel::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TelegramBotChannel.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
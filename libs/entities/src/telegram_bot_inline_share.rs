@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+/// A link a user posted through inline mode. Telegram does not say which chat
+/// an inline message went to, so the share belongs to the user alone.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "telegram_bot_inline_share")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub created_at: DateTimeUtc,
+    pub music_link_id: Uuid,
+    pub telegram_user_id: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::music_link::Entity",
+        from = "Column::MusicLinkId",
+        to = "super::music_link::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    MusicLink,
+}
+
+impl Related<super::music_link::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MusicLink.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -8,6 +8,7 @@ use uuid::Uuid;
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
+    pub country: Option<String>,
     pub telegram_user_id: i64,
     pub created_at: DateTimeUtc,
     pub telegram_bot_channel_id: Uuid,
@@ -3,11 +3,28 @@ pub use sea_orm_migration::prelude::*;
 mod m20250511_create_extensions;
 mod m20250512_create_telegram_bot_channel;
 mod m20250513_create_telegram_bot_user;
+// Applied migrations must not change, so the lint is silenced from here.
+#[allow(clippy::enum_variant_names)]
 mod m20250514_create_music_link;
 mod m20250515_create_telegram_bot_music_share;
 mod m20250516_create_telegram_bot_music_share_reaction;
 mod m20250517_add_llm_sentiment_analysis_column_to_telegram_bot_music_share_reaction;
 mod m20250518_add_last_interacted_at_columns;
+mod m20250520_create_music_link_platform;
+mod m20250521_add_metadata_columns_to_music_link;
+mod m20250522_create_music_link_negative_cache;
+mod m20250523_add_last_resolved_at_to_music_link;
+mod m20250524_add_kind_to_music_link;
+mod m20250525_add_match_confidence;
+mod m20250526_add_country_to_telegram_bot_tables;
+mod m20250527_add_availability_to_music_link_platform;
+mod m20250528_create_telegram_bot_channel_settings;
+mod m20250529_add_duplicate_share_mode_to_telegram_bot_channel_settings;
+mod m20250530_add_digest_to_telegram_bot_channel_settings;
+mod m20250601_add_country_to_music_link;
+mod m20250602_add_wrapped_to_telegram_bot_channel_settings;
+mod m20250603_create_telegram_bot_inline_share;
+mod m20250604_canonicalize_music_link_links;
 
 pub struct Migrator;
 
@@ -23,6 +40,21 @@ impl MigratorTrait for Migrator {
             Box::new(m20250516_create_telegram_bot_music_share_reaction::Migration),
             Box::new(m20250517_add_llm_sentiment_analysis_column_to_telegram_bot_music_share_reaction::Migration),
             Box::new(m20250518_add_last_interacted_at_columns::Migration),
+            Box::new(m20250520_create_music_link_platform::Migration),
+            Box::new(m20250521_add_metadata_columns_to_music_link::Migration),
+            Box::new(m20250522_create_music_link_negative_cache::Migration),
+            Box::new(m20250523_add_last_resolved_at_to_music_link::Migration),
+            Box::new(m20250524_add_kind_to_music_link::Migration),
+            Box::new(m20250525_add_match_confidence::Migration),
+            Box::new(m20250526_add_country_to_telegram_bot_tables::Migration),
+            Box::new(m20250527_add_availability_to_music_link_platform::Migration),
+            Box::new(m20250528_create_telegram_bot_channel_settings::Migration),
+            Box::new(m20250529_add_duplicate_share_mode_to_telegram_bot_channel_settings::Migration),
+            Box::new(m20250530_add_digest_to_telegram_bot_channel_settings::Migration),
+            Box::new(m20250601_add_country_to_music_link::Migration),
+            Box::new(m20250602_add_wrapped_to_telegram_bot_channel_settings::Migration),
+            Box::new(m20250603_create_telegram_bot_inline_share::Migration),
+            Box::new(m20250604_canonicalize_music_link_links::Migration),
         ]
     }
 }
@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20250514_create_music_link::MusicLink;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLinkPlatform {
+    Id,
+    Link,
+    Table,
+    Platform,
+    CreatedAt,
+    MusicLinkId,
+}
+
+/// Platforms that had their own column on `music_link`.
+static MIGRATED_PLATFORMS: [&str; 3] = ["spotify", "apple_music", "youtube_music"];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        manager
+            .create_table(
+                Table::create()
+                    .table(MusicLinkPlatform::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MusicLinkPlatform::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(PgFunc::gen_random_uuid()),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkPlatform::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkPlatform::MusicLinkId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkPlatform::Platform)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MusicLinkPlatform::Link).text().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-music_link_platform-music_link_id")
+                            .from(MusicLinkPlatform::Table, MusicLinkPlatform::MusicLinkId)
+                            .to(MusicLink::Table, MusicLink::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-music_link_platform-music_link_id_platform")
+                    .table(MusicLinkPlatform::Table)
+                    .col(MusicLinkPlatform::MusicLinkId)
+                    .col(MusicLinkPlatform::Platform)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-music_link_platform-link")
+                    .table(MusicLinkPlatform::Table)
+                    .col(MusicLinkPlatform::Link)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        for platform in MIGRATED_PLATFORMS {
+            db.execute_unprepared(&format!(
+                "
+INSERT INTO music_link_platform (music_link_id, platform, link)
+SELECT id, '{platform}', {platform}_link FROM music_link WHERE {platform}_link IS NOT NULL;
+
+ALTER TABLE music_link DROP COLUMN {platform}_link;
+                "
+            ))
+            .await?;
+        }
+        db.execute_unprepared(
+            "
+DROP TRIGGER IF EXISTS set_all_links_before_insert_or_update ON music_link;
+
+DROP FUNCTION IF EXISTS update_all_links();
+
+ALTER TABLE music_link DROP COLUMN all_links;
+            ",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLink {
+    Isrc,
+    Album,
+    Table,
+    Title,
+    Artists,
+    ArtworkUrl,
+    DurationMs,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MusicLink::Table)
+                    .add_column(ColumnDef::new(MusicLink::Title).text())
+                    .add_column(
+                        ColumnDef::new(MusicLink::Artists)
+                            .array(ColumnType::Text)
+                            .not_null()
+                            .default(Expr::cust("ARRAY[]::text[]")),
+                    )
+                    .add_column(ColumnDef::new(MusicLink::Album).text())
+                    .add_column(ColumnDef::new(MusicLink::ArtworkUrl).text())
+                    .add_column(ColumnDef::new(MusicLink::DurationMs).integer())
+                    .add_column(ColumnDef::new(MusicLink::Isrc).text())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-music_link-isrc")
+                    .table(MusicLink::Table)
+                    .col(MusicLink::Isrc)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
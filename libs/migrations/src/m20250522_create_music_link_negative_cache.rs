@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLinkNegativeCache {
+    Id,
+    Link,
+    Table,
+    Reason,
+    CreatedAt,
+    ExpiresAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MusicLinkNegativeCache::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MusicLinkNegativeCache::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(PgFunc::gen_random_uuid()),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkNegativeCache::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkNegativeCache::Link)
+                            .text()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkNegativeCache::Reason)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MusicLinkNegativeCache::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-music_link_negative_cache-expires_at")
+                    .table(MusicLinkNegativeCache::Table)
+                    .col(MusicLinkNegativeCache::ExpiresAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "
+ALTER TABLE music_link
+ADD COLUMN last_resolved_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE music_link SET last_resolved_at = created_at;
+
+ALTER TABLE music_link
+ALTER COLUMN last_resolved_at SET NOT NULL;
+
+ALTER TABLE music_link
+ALTER COLUMN last_resolved_at SET DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX idx_music_link_last_resolved_at ON music_link (last_resolved_at);
+        ",
+        )
+        .await?;
+        Ok(())
+    }
+}
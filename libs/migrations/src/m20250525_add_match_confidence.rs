@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLink {
+    Upc,
+    Table,
+}
+
+#[derive(Iden)]
+pub enum MusicLinkPlatform {
+    Table,
+    Confidence,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MusicLink::Table)
+                    .add_column(ColumnDef::new(MusicLink::Upc).text())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-music_link-upc")
+                    .table(MusicLink::Table)
+                    .col(MusicLink::Upc)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MusicLinkPlatform::Table)
+                    .add_column(
+                        ColumnDef::new(MusicLinkPlatform::Confidence)
+                            .small_integer()
+                            .not_null()
+                            .default(100),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
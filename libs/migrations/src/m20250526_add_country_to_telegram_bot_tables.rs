@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum TelegramBotChannel {
+    Table,
+    Country,
+}
+
+#[derive(Iden)]
+pub enum TelegramBotUser {
+    Table,
+    Country,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TelegramBotChannel::Table)
+                    .add_column(ColumnDef::new(TelegramBotChannel::Country).text())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TelegramBotUser::Table)
+                    .add_column(ColumnDef::new(TelegramBotUser::Country).text())
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLinkPlatform {
+    Table,
+    IsAvailable,
+    AvailabilityCheckedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MusicLinkPlatform::Table)
+                    .add_column(
+                        ColumnDef::new(MusicLinkPlatform::IsAvailable)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .add_column(
+                        ColumnDef::new(MusicLinkPlatform::AvailabilityCheckedAt)
+                            .timestamp_with_time_zone(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-music_link_platform-availability_checked_at")
+                    .table(MusicLinkPlatform::Table)
+                    .col(MusicLinkPlatform::AvailabilityCheckedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20250512_create_telegram_bot_channel::TelegramBotChannel;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum TelegramBotChannelSettings {
+    Id,
+    Table,
+    Platforms,
+    CreatedAt,
+    DeleteOriginal,
+    ReplyLanguage,
+    FailureReactionEmoji,
+    TelegramBotChannelId,
+    FailureReactionEnabled,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TelegramBotChannelSettings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(PgFunc::gen_random_uuid()),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::TelegramBotChannelId)
+                            .uuid()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::DeleteOriginal)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::FailureReactionEnabled)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::FailureReactionEmoji)
+                            .text()
+                            .not_null()
+                            .default("😢"),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::Platforms)
+                            .array(ColumnType::Text)
+                            .not_null()
+                            .default(Expr::cust("ARRAY[]::text[]")),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotChannelSettings::ReplyLanguage)
+                            .text()
+                            .not_null()
+                            .default("en"),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-telegram_bot_channel_settings-channel_id")
+                            .from(
+                                TelegramBotChannelSettings::Table,
+                                TelegramBotChannelSettings::TelegramBotChannelId,
+                            )
+                            .to(TelegramBotChannel::Table, TelegramBotChannel::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum TelegramBotChannelSettings {
+    Table,
+    DuplicateShareMode,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TelegramBotChannelSettings::Table)
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::DuplicateShareMode)
+                            .text()
+                            .not_null()
+                            .default("annotate"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum TelegramBotChannelSettings {
+    Table,
+    DigestEnabled,
+    DigestWeekday,
+    DigestHour,
+    Timezone,
+    DigestLastSentAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TelegramBotChannelSettings::Table)
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::DigestEnabled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::DigestWeekday)
+                            .small_integer()
+                            .not_null()
+                            .default(7),
+                    )
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::DigestHour)
+                            .small_integer()
+                            .not_null()
+                            .default(18),
+                    )
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::Timezone)
+                            .text()
+                            .not_null()
+                            .default("UTC"),
+                    )
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::DigestLastSentAt)
+                            .timestamp_with_time_zone(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLink {
+    Table,
+    Country,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MusicLink::Table)
+                    .add_column(
+                        ColumnDef::new(MusicLink::Country)
+                            .text()
+                            .not_null()
+                            .default("US"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
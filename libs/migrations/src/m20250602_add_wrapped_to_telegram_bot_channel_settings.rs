@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum TelegramBotChannelSettings {
+    Table,
+    DigestEnabled,
+    WrappedEnabled,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TelegramBotChannelSettings::Table)
+                    .add_column(
+                        ColumnDef::new(TelegramBotChannelSettings::WrappedEnabled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Chats that had the digest on got the recaps along with it so far.
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(TelegramBotChannelSettings::Table)
+                    .value(
+                        TelegramBotChannelSettings::WrappedEnabled,
+                        Expr::col(TelegramBotChannelSettings::DigestEnabled),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
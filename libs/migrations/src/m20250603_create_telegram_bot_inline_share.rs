@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20250514_create_music_link::MusicLink;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum TelegramBotInlineShare {
+    Id,
+    Table,
+    CreatedAt,
+    MusicLinkId,
+    TelegramUserId,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TelegramBotInlineShare::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TelegramBotInlineShare::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(PgFunc::gen_random_uuid()),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotInlineShare::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotInlineShare::MusicLinkId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TelegramBotInlineShare::TelegramUserId)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-telegram_bot_inline_share-music_link_id")
+                            .from(
+                                TelegramBotInlineShare::Table,
+                                TelegramBotInlineShare::MusicLinkId,
+                            )
+                            .to(MusicLink::Table, MusicLink::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-telegram_bot_inline_share-telegram_user_id")
+                    .table(TelegramBotInlineShare::Table)
+                    .col(TelegramBotInlineShare::TelegramUserId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
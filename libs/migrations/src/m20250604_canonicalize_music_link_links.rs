@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use sea_orm_migration::{prelude::*, sea_orm::prelude::Uuid};
+use services::canonicalize_url;
+
+/// Rewrites the links copied over from the per-platform columns, which were
+/// stored as shared, into the canonical form links are looked up by. A link
+/// whose canonical form is already stored is dropped, and the music link it
+/// belonged to finds the platform again on its next refresh.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum MusicLink {
+    Id,
+    Table,
+    EquivalentLinks,
+}
+
+#[derive(Iden)]
+pub enum MusicLinkPlatform {
+    Id,
+    Link,
+    Table,
+    CreatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let builder = db.get_database_backend();
+        let platform_links = db
+            .query_all(
+                builder.build(
+                    Query::select()
+                        .columns([MusicLinkPlatform::Id, MusicLinkPlatform::Link])
+                        .from(MusicLinkPlatform::Table)
+                        .order_by(MusicLinkPlatform::CreatedAt, Order::Asc)
+                        .order_by(MusicLinkPlatform::Id, Order::Asc),
+                ),
+            )
+            .await?
+            .into_iter()
+            .map(|row| {
+                let id: Uuid = row.try_get("", "id")?;
+                let link: String = row.try_get("", "link")?;
+                let canonical = canonicalize_url(&link);
+                Ok((id, link, canonical))
+            })
+            .collect::<Result<Vec<_>, DbErr>>()?;
+        let mut taken: HashSet<String> = platform_links
+            .iter()
+            .filter(|(_, link, canonical)| link == canonical)
+            .map(|(_, link, _)| link.clone())
+            .collect();
+        for (id, link, canonical) in platform_links {
+            if link == canonical {
+                continue;
+            }
+            let condition = Expr::col(MusicLinkPlatform::Id).eq(id);
+            if taken.insert(canonical.clone()) {
+                manager
+                    .exec_stmt(
+                        Query::update()
+                            .table(MusicLinkPlatform::Table)
+                            .value(MusicLinkPlatform::Link, canonical)
+                            .and_where(condition)
+                            .to_owned(),
+                    )
+                    .await?;
+            } else {
+                manager
+                    .exec_stmt(
+                        Query::delete()
+                            .from_table(MusicLinkPlatform::Table)
+                            .and_where(condition)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+
+        let music_links = db
+            .query_all(
+                builder.build(
+                    Query::select()
+                        .columns([MusicLink::Id, MusicLink::EquivalentLinks])
+                        .from(MusicLink::Table),
+                ),
+            )
+            .await?;
+        for row in music_links {
+            let id: Uuid = row.try_get("", "id")?;
+            let links: Vec<String> = row.try_get("", "equivalent_links")?;
+            let mut seen = HashSet::new();
+            let canonical: Vec<String> = links
+                .iter()
+                .map(|link| canonicalize_url(link))
+                .filter(|link| seen.insert(link.clone()))
+                .collect();
+            if canonical == links {
+                continue;
+            }
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(MusicLink::Table)
+                        .value(MusicLink::EquivalentLinks, canonical)
+                        .and_where(Expr::col(MusicLink::Id).eq(id))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
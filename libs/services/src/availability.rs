@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use entities::{
+    music_link_platform,
+    prelude::{MusicLink, MusicLinkPlatform},
+};
+use reqwest::{Client, StatusCode};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    prelude::Expr,
+    sea_query::{NullOrdering, Order},
+};
+
+use crate::{
+    MusicLinkService,
+    config::LinkAvailabilityConfig,
+    error::{MusicLinkError, Result},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LinkAvailabilitySummary {
+    pub checked: u64,
+    pub dead: u64,
+    pub unknown: u64,
+    pub re_resolved: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkAvailability {
+    Available,
+    Dead,
+    /// The platform did not give a clear answer, such as a rate limit or an error.
+    Unknown,
+}
+
+/// What a check means for a platform link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AvailabilityChange {
+    is_available: bool,
+    /// The link worked before, so its music link is re-resolved for a replacement.
+    went_dead: bool,
+    /// Cached responses for the music link no longer match and are invalidated.
+    changed: bool,
+}
+
+impl LinkAvailability {
+    /// Applies the check to a link that was `was_available` before. Unclear
+    /// answers keep the previous state.
+    fn apply(self, was_available: bool) -> AvailabilityChange {
+        let is_available = match self {
+            LinkAvailability::Available => true,
+            LinkAvailability::Dead => false,
+            LinkAvailability::Unknown => was_available,
+        };
+        AvailabilityChange {
+            is_available,
+            went_dead: was_available && !is_available,
+            changed: was_available != is_available,
+        }
+    }
+}
+
+/// Checks a link with a HEAD request, falling back to GET for platforms that
+/// do not support HEAD. Only a 404 or 410 counts as dead.
+///
+/// Some platforms, Spotify and Apple Music among them, answer removed or
+/// region-locked entries with a 200 and a "not available" page. Those soft
+/// 404s are reported as available, since telling them apart would mean
+/// parsing each platform's markup, so such links stay in responses.
+async fn probe_link(client: &Client, link: &str) -> LinkAvailability {
+    let response = match client.head(link).send().await {
+        Ok(response)
+            if matches!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            ) =>
+        {
+            client.get(link).send().await
+        }
+        result => result,
+    };
+    match response {
+        Ok(response) if response.status().is_success() => LinkAvailability::Available,
+        Ok(response) if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) => {
+            LinkAvailability::Dead
+        }
+        Ok(response) => {
+            tracing::debug!("Checking {} responded with {}", link, response.status());
+            LinkAvailability::Unknown
+        }
+        Err(e) => {
+            tracing::debug!("Checking {} failed: {}", link, e);
+            LinkAvailability::Unknown
+        }
+    }
+}
+
+impl MusicLinkService {
+    /// Checks the platform links that were never checked or were checked
+    /// longer ago than `check_after`, and records whether they still work.
+    ///
+    /// Dead links are hidden from responses, and the music links they belong
+    /// to are re-resolved so that a replacement can be found.
+    pub async fn check_link_availability(
+        &self,
+        config: &LinkAvailabilityConfig,
+        db: &DatabaseConnection,
+    ) -> Result<LinkAvailabilitySummary> {
+        let checked_before =
+            Utc::now() - chrono::Duration::from_std(config.check_after).unwrap_or_default();
+        let candidates = MusicLinkPlatform::find()
+            .filter(
+                Condition::any()
+                    .add(music_link_platform::Column::AvailabilityCheckedAt.is_null())
+                    .add(music_link_platform::Column::AvailabilityCheckedAt.lt(checked_before)),
+            )
+            .order_by_with_nulls(
+                music_link_platform::Column::AvailabilityCheckedAt,
+                Order::Asc,
+                NullOrdering::First,
+            )
+            .limit(config.budget)
+            .all(db)
+            .await?;
+        tracing::info!("Found {} platform links to check", candidates.len());
+
+        let mut summary = LinkAvailabilitySummary::default();
+        let mut newly_dead = HashSet::new();
+        for platform_link in candidates {
+            summary.checked += 1;
+            let availability = probe_link(&self.client, &platform_link.link).await;
+            match availability {
+                LinkAvailability::Available => {}
+                LinkAvailability::Dead => summary.dead += 1,
+                LinkAvailability::Unknown => summary.unknown += 1,
+            }
+            let change = availability.apply(platform_link.is_available);
+            if change.went_dead {
+                tracing::info!("Platform link went dead: {}", platform_link.link);
+                newly_dead.insert(platform_link.music_link_id);
+            }
+            MusicLinkPlatform::update_many()
+                .filter(music_link_platform::Column::Id.eq(platform_link.id))
+                .col_expr(
+                    music_link_platform::Column::IsAvailable,
+                    Expr::value(change.is_available),
+                )
+                .col_expr(
+                    music_link_platform::Column::AvailabilityCheckedAt,
+                    Expr::value(Utc::now()),
+                )
+                .exec(db)
+                .await?;
+            if change.changed {
+                self.cache.invalidate(platform_link.music_link_id);
+            }
+        }
+
+        for id in newly_dead {
+            let Some(music_link) = MusicLink::find_by_id(id).one(db).await? else {
+                continue;
+            };
+            let result = self.refresh_music_link(music_link, db).await;
+            self.cache.invalidate(id);
+            match result {
+                Ok(_) => summary.re_resolved += 1,
+                Err(MusicLinkError::Database(e)) => return Err(e.into()),
+                Err(e) => {
+                    // The refresh job picks the link up later since it is now
+                    // missing a platform.
+                    tracing::warn!("Stopping re-resolution at music link {}: {}", id, e);
+                    break;
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        http::{Method, StatusCode as Status},
+        routing::any,
+    };
+
+    use super::*;
+    use crate::test_server::serve;
+
+    /// Serves `/link` answering HEAD with `head` and GET with `get`.
+    async fn link(head: Status, get: Status) -> String {
+        let router =
+            Router::new().route(
+                "/link",
+                any(move |method: Method| async move {
+                    if method == Method::HEAD { head } else { get }
+                }),
+            );
+        format!("{}/link", serve(router).await)
+    }
+
+    async fn probe(head: Status, get: Status) -> LinkAvailability {
+        probe_link(&Client::new(), &link(head, get).await).await
+    }
+
+    #[tokio::test]
+    async fn probes_with_head() {
+        assert_eq!(
+            probe(Status::OK, Status::NOT_FOUND).await,
+            LinkAvailability::Available
+        );
+        assert_eq!(
+            probe(Status::NOT_FOUND, Status::OK).await,
+            LinkAvailability::Dead
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_get_when_head_is_not_supported() {
+        assert_eq!(
+            probe(Status::METHOD_NOT_ALLOWED, Status::OK).await,
+            LinkAvailability::Available
+        );
+        assert_eq!(
+            probe(Status::NOT_IMPLEMENTED, Status::GONE).await,
+            LinkAvailability::Dead
+        );
+    }
+
+    #[tokio::test]
+    async fn only_404_and_410_are_dead() {
+        assert_eq!(
+            probe(Status::GONE, Status::GONE).await,
+            LinkAvailability::Dead
+        );
+        for status in [
+            Status::FORBIDDEN,
+            Status::TOO_MANY_REQUESTS,
+            Status::INTERNAL_SERVER_ERROR,
+        ] {
+            assert_eq!(probe(status, status).await, LinkAvailability::Unknown);
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_links_are_unknown() {
+        let availability = probe_link(&Client::new(), "http://127.0.0.1:9/link").await;
+        assert_eq!(availability, LinkAvailability::Unknown);
+    }
+
+    #[test]
+    fn dead_link_is_invalidated_and_re_resolved() {
+        let change = LinkAvailability::Dead.apply(true);
+        assert_eq!(
+            change,
+            AvailabilityChange {
+                is_available: false,
+                went_dead: true,
+                changed: true,
+            }
+        );
+    }
+
+    #[test]
+    fn revived_link_is_invalidated_without_re_resolving() {
+        let change = LinkAvailability::Available.apply(false);
+        assert!(change.is_available);
+        assert!(change.changed);
+        assert!(!change.went_dead);
+    }
+
+    #[test]
+    fn unclear_answers_keep_the_previous_state() {
+        for was_available in [true, false] {
+            let change = LinkAvailability::Unknown.apply(was_available);
+            assert_eq!(change.is_available, was_available);
+            assert!(!change.changed);
+            assert!(!change.went_dead);
+        }
+    }
+
+    #[test]
+    fn dead_link_checked_again_does_not_re_resolve() {
+        let change = LinkAvailability::Dead.apply(false);
+        assert!(!change.changed);
+        assert!(!change.went_dead);
+    }
+}
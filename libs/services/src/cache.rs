@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use entities::music_link;
+use hashlink::LruCache;
+use uuid::Uuid;
+
+use crate::{
+    config::MusicLinkCacheConfig,
+    models::{MusicPlatform, PlatformLink},
+};
+
+#[derive(Debug, Clone)]
+pub struct CachedMusicLink {
+    pub music_link: music_link::Model,
+    pub platform_links: HashMap<MusicPlatform, PlatformLink>,
+}
+
+struct CacheEntry {
+    value: CachedMusicLink,
+    expires_at: Instant,
+}
+
+/// The cached entries, along with the keys each music link is cached under so
+/// that `invalidate` does not have to scan every entry.
+struct Entries {
+    lru: LruCache<String, CacheEntry>,
+    keys_by_id: HashMap<Uuid, HashSet<String>>,
+}
+
+impl Entries {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.lru.remove(key) {
+            self.unindex(entry.value.music_link.id, key);
+        }
+    }
+
+    fn unindex(&mut self, id: Uuid, key: &str) {
+        if let Some(keys) = self.keys_by_id.get_mut(&id) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.keys_by_id.remove(&id);
+            }
+        }
+    }
+}
+
+/// Size and TTL bounded cache of stored music links keyed by country and link,
+/// along with the interactions that still have to be written to the database.
+pub struct MusicLinkCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+    touched: Mutex<HashSet<Uuid>>,
+}
+
+impl MusicLinkCache {
+    pub fn new(config: &MusicLinkCacheConfig) -> Self {
+        let entries = Entries {
+            lru: LruCache::new(config.capacity),
+            keys_by_id: HashMap::new(),
+        };
+        Self {
+            ttl: config.ttl,
+            entries: Mutex::new(entries),
+            touched: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn get(&self, link: &str) -> Option<CachedMusicLink> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.lru.get(link) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(link);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, link: String, value: CachedMusicLink) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.lru.capacity() == 0 {
+            return;
+        }
+        entries.remove(&link);
+        if entries.lru.len() >= entries.lru.capacity()
+            && let Some((evicted_link, evicted)) = entries.lru.remove_lru()
+        {
+            entries.unindex(evicted.value.music_link.id, &evicted_link);
+        }
+        entries
+            .keys_by_id
+            .entry(value.music_link.id)
+            .or_default()
+            .insert(link.clone());
+        let entry = CacheEntry {
+            value,
+            expires_at: Instant::now() + self.ttl,
+        };
+        entries.lru.insert(link, entry);
+    }
+
+    /// Drops every cached link that points at the music link `id`.
+    pub fn invalidate(&self, id: Uuid) {
+        let mut entries = self.entries.lock().unwrap();
+        for link in entries.keys_by_id.remove(&id).unwrap_or_default() {
+            entries.lru.remove(&link);
+        }
+    }
+
+    /// Records an interaction with the music link `id` to be flushed later.
+    pub fn touch(&self, id: Uuid) {
+        self.touched.lock().unwrap().insert(id);
+    }
+
+    pub fn take_touches(&self) -> Vec<Uuid> {
+        self.touched.lock().unwrap().drain().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::MusicEntityKind;
+
+    fn cache(capacity: usize, ttl: Duration) -> MusicLinkCache {
+        MusicLinkCache::new(&MusicLinkCacheConfig {
+            capacity,
+            ttl,
+            ..Default::default()
+        })
+    }
+
+    fn cached(id: Uuid) -> CachedMusicLink {
+        let music_link = music_link::Model {
+            id,
+            kind: MusicEntityKind::Song,
+            upc: None,
+            isrc: None,
+            album: None,
+            title: None,
+            artists: vec![],
+            country: "US".to_owned(),
+            created_at: Utc::now(),
+            artwork_url: None,
+            duration_ms: None,
+            equivalent_links: vec![],
+            last_interacted_at: Utc::now(),
+            last_resolved_at: Utc::now(),
+        };
+        CachedMusicLink {
+            music_link,
+            platform_links: HashMap::new(),
+        }
+    }
+
+    fn cached_id(cache: &MusicLinkCache, link: &str) -> Option<Uuid> {
+        cache.get(link).map(|cached| cached.music_link.id)
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let cache = cache(10, Duration::from_millis(20));
+        let id = Uuid::from_u128(1);
+        cache.insert("US:a".to_owned(), cached(id));
+        assert_eq!(cached_id(&cache, "US:a"), Some(id));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cached_id(&cache, "US:a"), None);
+        assert!(cache.entries.lock().unwrap().keys_by_id.is_empty());
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_at_capacity() {
+        let cache = cache(2, Duration::from_secs(60));
+        let (a, b, c) = (Uuid::from_u128(2), Uuid::from_u128(3), Uuid::from_u128(4));
+        cache.insert("US:a".to_owned(), cached(a));
+        cache.insert("US:b".to_owned(), cached(b));
+        // Reading `a` makes `b` the least recently used.
+        cache.get("US:a");
+        cache.insert("US:c".to_owned(), cached(c));
+
+        assert_eq!(cached_id(&cache, "US:a"), Some(a));
+        assert_eq!(cached_id(&cache, "US:b"), None);
+        assert_eq!(cached_id(&cache, "US:c"), Some(c));
+        assert!(!cache.entries.lock().unwrap().keys_by_id.contains_key(&b));
+    }
+
+    #[test]
+    fn invalidate_drops_every_key_of_the_music_link() {
+        let cache = cache(10, Duration::from_secs(60));
+        let (id, other) = (Uuid::from_u128(5), Uuid::from_u128(6));
+        cache.insert("US:a".to_owned(), cached(id));
+        cache.insert("DE:a".to_owned(), cached(id));
+        cache.insert("US:b".to_owned(), cached(other));
+
+        cache.invalidate(id);
+
+        assert_eq!(cached_id(&cache, "US:a"), None);
+        assert_eq!(cached_id(&cache, "DE:a"), None);
+        assert_eq!(cached_id(&cache, "US:b"), Some(other));
+    }
+
+    #[test]
+    fn replacing_a_key_moves_it_to_the_new_music_link() {
+        let cache = cache(10, Duration::from_secs(60));
+        let (old, new) = (Uuid::from_u128(7), Uuid::from_u128(8));
+        cache.insert("US:a".to_owned(), cached(old));
+        cache.insert("US:a".to_owned(), cached(new));
+
+        cache.invalidate(old);
+        assert_eq!(cached_id(&cache, "US:a"), Some(new));
+        cache.invalidate(new);
+        assert_eq!(cached_id(&cache, "US:a"), None);
+    }
+
+    #[test]
+    fn touches_are_taken_once() {
+        let cache = cache(10, Duration::from_secs(60));
+        let id = Uuid::from_u128(9);
+        cache.touch(id);
+        cache.touch(id);
+
+        assert_eq!(cache.take_touches(), vec![id]);
+        assert!(cache.take_touches().is_empty());
+    }
+}
@@ -0,0 +1,416 @@
+use reqwest::{Client, Url};
+
+use crate::models::{MusicEntityKind, MusicPlatform};
+
+/// Hosts that only redirect to the real page and therefore need a request to expand.
+static REDIRECT_SHORT_LINK_HOSTS: [&str; 5] = [
+    "spotify.link",
+    "spotify.app.link",
+    "link.deezer.com",
+    "deezer.page.link",
+    "on.soundcloud.com",
+];
+
+/// Query parameters that never change which page a link points to.
+static TRACKING_PARAMS: [&str; 11] = [
+    "si",
+    "nd",
+    "pp",
+    "ref",
+    "gclid",
+    "fbclid",
+    "igshid",
+    "context",
+    "feature",
+    "app_source",
+    "deferred_deeplink",
+];
+
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
+}
+
+fn path_segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Path segments naming the kind of entity that the following segment is the id of.
+static SPOTIFY_KINDS: [&str; 6] = ["track", "album", "artist", "playlist", "episode", "show"];
+static DEEZER_KINDS: [&str; 6] = ["track", "album", "artist", "playlist", "episode", "show"];
+static TIDAL_KINDS: [&str; 6] = ["track", "album", "artist", "playlist", "video", "mix"];
+
+/// Drops the parts of a Spotify path that do not identify the entity: the
+/// `intl-xx` locale, the `embed` player and the legacy `user/<name>` owner.
+fn spotify_entity_segments<'a>(mut segments: &'a [&'a str]) -> &'a [&'a str] {
+    loop {
+        segments = match segments {
+            [first, rest @ ..] if first.starts_with("intl-") || *first == "embed" => rest,
+            ["user", _, rest @ ..] if !rest.is_empty() => rest,
+            _ => return segments,
+        };
+    }
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Maps links whose platform id can be read from the url to a single canonical form.
+fn canonicalize_known_platform(url: &Url) -> Option<String> {
+    let host = url.host_str()?.trim_start_matches("www.");
+    let segments = path_segments(url);
+    match host {
+        "open.spotify.com" | "play.spotify.com" => match spotify_entity_segments(&segments) {
+            [kind, id, ..] if SPOTIFY_KINDS.contains(kind) => {
+                Some(format!("https://open.spotify.com/{kind}/{id}"))
+            }
+            _ => None,
+        },
+        "music.youtube.com" => {
+            let id = query_param(url, "v")?;
+            Some(format!("https://music.youtube.com/watch?v={id}"))
+        }
+        "youtube.com" | "m.youtube.com" => match segments.as_slice() {
+            ["watch"] => {
+                let id = query_param(url, "v")?;
+                Some(format!("https://www.youtube.com/watch?v={id}"))
+            }
+            ["shorts", id] => Some(format!("https://www.youtube.com/watch?v={id}")),
+            _ => None,
+        },
+        "youtu.be" => {
+            let id = segments.first()?;
+            Some(format!("https://www.youtube.com/watch?v={id}"))
+        }
+        "music.apple.com" => {
+            // The storefront and the slug change between regions and
+            // renames, only the id identifies the page.
+            let segments = match segments.first() {
+                Some(first) if first.len() == 2 => &segments[1..],
+                _ => &segments[..],
+            };
+            let [kind, .., id] = segments else {
+                return None;
+            };
+            match query_param(url, "i") {
+                Some(track) => Some(format!("https://music.apple.com/{kind}/{id}?i={track}")),
+                None => Some(format!("https://music.apple.com/{kind}/{id}")),
+            }
+        }
+        "deezer.com" => {
+            let segments = match segments.first() {
+                Some(first) if first.len() == 2 => &segments[1..],
+                _ => &segments[..],
+            };
+            match segments {
+                [kind, id, ..] if DEEZER_KINDS.contains(kind) => {
+                    Some(format!("https://www.deezer.com/{kind}/{id}"))
+                }
+                _ => None,
+            }
+        }
+        "tidal.com" | "listen.tidal.com" => {
+            // Tracks opened from an album keep the album in the path, so the
+            // last kind and id pair is the one the link points to.
+            let (kind, id) = segments.windows(2).rev().find_map(|pair| match pair {
+                [kind, id] if TIDAL_KINDS.contains(kind) => Some((kind, id)),
+                _ => None,
+            })?;
+            Some(format!("https://tidal.com/browse/{kind}/{id}"))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the canonical form of a link used for both cache lookups and storage.
+///
+/// Hosts are lowercased, tracking parameters and fragments are removed, and
+/// links for platforms with well-known url schemes are reduced to their id.
+/// Anything that cannot be parsed as a url is returned unchanged.
+pub fn canonicalize_url(link: &str) -> String {
+    let Ok(mut url) = Url::parse(link.trim()) else {
+        return link.to_owned();
+    };
+    if let Some(canonical) = canonicalize_known_platform(&url) {
+        return canonical;
+    }
+    url.set_fragment(None);
+    if url.path().len() > 1 && url.path().ends_with('/') {
+        let trimmed = url.path().trim_end_matches('/').to_owned();
+        url.set_path(&trimmed);
+    }
+    let kept_params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if kept_params.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept_params);
+    }
+    url.to_string()
+}
+
+/// Follows redirects for known short-link domains and canonicalizes the result.
+///
+/// Links on other hosts, and short links that fail to expand, are only
+/// canonicalized.
+pub async fn expand_and_canonicalize_url(client: &Client, link: &str) -> String {
+    let is_short_link = Url::parse(link.trim())
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_owned()))
+        .is_some_and(|host| REDIRECT_SHORT_LINK_HOSTS.contains(&host.as_str()));
+    if !is_short_link {
+        return canonicalize_url(link);
+    }
+    match client.get(link.trim()).send().await {
+        Ok(response) => {
+            tracing::debug!("Expanded short link {} to {}", link, response.url());
+            canonicalize_url(response.url().as_str())
+        }
+        Err(e) => {
+            tracing::warn!("Failed to expand short link {}: {}", link, e);
+            canonicalize_url(link)
+        }
+    }
+}
+
+/// Detects which platform a canonical link belongs to.
+pub fn detect_platform(link: &str) -> Option<MusicPlatform> {
+    let url = Url::parse(link).ok()?;
+    let host = url.host_str()?.trim_start_matches("www.");
+    let platform = match host {
+        "open.spotify.com" => MusicPlatform::Spotify,
+        "music.apple.com" => MusicPlatform::AppleMusic,
+        "music.youtube.com" => MusicPlatform::YoutubeMusic,
+        "youtube.com" | "m.youtube.com" | "youtu.be" => MusicPlatform::Youtube,
+        "deezer.com" => MusicPlatform::Deezer,
+        "tidal.com" | "listen.tidal.com" => MusicPlatform::Tidal,
+        "soundcloud.com" | "m.soundcloud.com" => MusicPlatform::Soundcloud,
+        "pandora.com" => MusicPlatform::Pandora,
+        "audiomack.com" => MusicPlatform::Audiomack,
+        "anghami.com" | "play.anghami.com" => MusicPlatform::Anghami,
+        "boomplay.com" => MusicPlatform::Boomplay,
+        "audius.co" => MusicPlatform::Audius,
+        host if host.ends_with(".bandcamp.com") => MusicPlatform::Bandcamp,
+        host if host.starts_with("music.amazon.") => MusicPlatform::AmazonMusic,
+        host if host.starts_with("music.yandex.") => MusicPlatform::Yandex,
+        _ => return None,
+    };
+    Some(platform)
+}
+
+/// Detects what kind of entity a canonical link points to, when the url makes it obvious.
+pub fn detect_entity_kind(link: &str) -> Option<MusicEntityKind> {
+    let url = Url::parse(link).ok()?;
+    let platform = detect_platform(link)?;
+    let segments = path_segments(&url);
+    let kind_segment = segments.iter().find_map(|segment| match *segment {
+        "track" | "song" => Some(MusicEntityKind::Song),
+        "album" => Some(MusicEntityKind::Album),
+        "artist" | "channel" => Some(MusicEntityKind::Artist),
+        "playlist" | "sets" => Some(MusicEntityKind::Playlist),
+        _ => None,
+    });
+    match platform {
+        MusicPlatform::AppleMusic if kind_segment == Some(MusicEntityKind::Album) => {
+            match query_param(&url, "i") {
+                Some(_) => Some(MusicEntityKind::Song),
+                None => Some(MusicEntityKind::Album),
+            }
+        }
+        MusicPlatform::YoutubeMusic | MusicPlatform::Youtube => match segments.first() {
+            Some(&"watch") => Some(MusicEntityKind::Song),
+            Some(&"playlist") => match query_param(&url, "list") {
+                Some(list) if list.starts_with("OLAK5uy_") => Some(MusicEntityKind::Album),
+                _ => Some(MusicEntityKind::Playlist),
+            },
+            Some(&"channel") => Some(MusicEntityKind::Artist),
+            _ => None,
+        },
+        _ => kind_segment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_canonical(links: &[&str], expected: &str) {
+        for link in links {
+            assert_eq!(canonicalize_url(link), expected, "{link}");
+        }
+    }
+
+    #[test]
+    fn spotify_links_are_reduced_to_kind_and_id() {
+        assert_canonical(
+            &[
+                "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+                "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC?si=abc123",
+                "https://open.spotify.com/intl-de/track/4uLU6hMCjMI75M1A2tKUQC",
+                "https://play.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC#top",
+            ],
+            "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+        );
+    }
+
+    #[test]
+    fn spotify_embed_links_are_reduced_to_kind_and_id() {
+        assert_canonical(
+            &[
+                "https://open.spotify.com/embed/track/4uLU6hMCjMI75M1A2tKUQC",
+                "https://open.spotify.com/embed/track/4uLU6hMCjMI75M1A2tKUQC?utm_source=generator",
+                "https://open.spotify.com/intl-de/embed/track/4uLU6hMCjMI75M1A2tKUQC",
+            ],
+            "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+        );
+        assert_ne!(
+            canonicalize_url("https://open.spotify.com/embed/track/4uLU6hMCjMI75M1A2tKUQC"),
+            canonicalize_url("https://open.spotify.com/embed/track/7GhIk7Il098yCjg4BQjzvb"),
+        );
+    }
+
+    #[test]
+    fn spotify_intl_links_drop_the_locale() {
+        assert_canonical(
+            &[
+                "https://open.spotify.com/intl-pt/album/6N9PS4QXF1D0OWPk0Sxtb4",
+                "https://open.spotify.com/intl-ja/album/6N9PS4QXF1D0OWPk0Sxtb4?si=x",
+            ],
+            "https://open.spotify.com/album/6N9PS4QXF1D0OWPk0Sxtb4",
+        );
+    }
+
+    #[test]
+    fn legacy_spotify_user_playlists_drop_the_owner() {
+        assert_canonical(
+            &[
+                "https://open.spotify.com/user/spotify/playlist/37i9dQZF1DXcBWIGoYBM5M",
+                "https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M",
+            ],
+            "https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M",
+        );
+        assert_ne!(
+            canonicalize_url(
+                "https://open.spotify.com/user/spotify/playlist/37i9dQZF1DXcBWIGoYBM5M"
+            ),
+            canonicalize_url(
+                "https://open.spotify.com/user/spotify/playlist/37i9dQZEVXbMDoHDwVN2tF"
+            ),
+        );
+    }
+
+    #[test]
+    fn spotify_user_profiles_keep_their_path() {
+        assert_canonical(
+            &["https://open.spotify.com/user/spotify?si=abc"],
+            "https://open.spotify.com/user/spotify",
+        );
+    }
+
+    #[test]
+    fn apple_music_links_drop_storefront_and_slug() {
+        assert_canonical(
+            &[
+                "https://music.apple.com/us/album/never-gonna-give-you-up/1559523357?i=1559523359",
+                "https://music.apple.com/gb/album/whenever-you-need-somebody/1559523357?i=1559523359&ls=1",
+                "https://music.apple.com/album/1559523357?i=1559523359",
+            ],
+            "https://music.apple.com/album/1559523357?i=1559523359",
+        );
+        assert_canonical(
+            &[
+                "https://music.apple.com/us/album/whenever-you-need-somebody/1559523357",
+                "https://music.apple.com/de/album/1559523357",
+            ],
+            "https://music.apple.com/album/1559523357",
+        );
+        assert_canonical(
+            &["https://music.apple.com/us/artist/rick-astley/669771"],
+            "https://music.apple.com/artist/669771",
+        );
+    }
+
+    #[test]
+    fn youtube_music_links_keep_only_the_video_id() {
+        assert_canonical(
+            &[
+                "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://music.youtube.com/watch?v=dQw4w9WgXcQ&feature=share&list=RDAMVM",
+            ],
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+        );
+    }
+
+    #[test]
+    fn youtube_links_share_one_form() {
+        assert_canonical(
+            &[
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://m.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+                "https://youtu.be/dQw4w9WgXcQ?si=abc",
+                "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            ],
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        );
+    }
+
+    #[test]
+    fn deezer_links_drop_the_language() {
+        assert_canonical(
+            &[
+                "https://www.deezer.com/track/3135556",
+                "https://www.deezer.com/en/track/3135556",
+                "https://deezer.com/fr/track/3135556?utm_source=share",
+            ],
+            "https://www.deezer.com/track/3135556",
+        );
+    }
+
+    #[test]
+    fn tidal_links_use_the_browse_form() {
+        assert_canonical(
+            &[
+                "https://tidal.com/browse/track/77646170",
+                "https://tidal.com/track/77646170",
+                "https://listen.tidal.com/track/77646170?u",
+                "https://listen.tidal.com/album/77646168/track/77646170",
+            ],
+            "https://tidal.com/browse/track/77646170",
+        );
+    }
+
+    #[test]
+    fn deezer_paths_without_an_entity_keep_their_path() {
+        assert_canonical(
+            &["https://www.deezer.com/en/profile/123?utm_source=x"],
+            "https://www.deezer.com/en/profile/123",
+        );
+    }
+
+    #[test]
+    fn other_links_only_lose_tracking_and_fragments() {
+        assert_canonical(
+            &[
+                "https://Artist.Bandcamp.com/track/song/?utm_source=x&fbclid=y#play",
+                "https://artist.bandcamp.com/track/song",
+            ],
+            "https://artist.bandcamp.com/track/song",
+        );
+        assert_canonical(
+            &["https://soundcloud.com/artist/song?in=artist/sets/x&si=abc"],
+            "https://soundcloud.com/artist/song?in=artist%2Fsets%2Fx",
+        );
+    }
+
+    #[test]
+    fn text_that_is_not_a_url_is_unchanged() {
+        assert_canonical(&["rick astley - never gonna"], "rick astley - never gonna");
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use reqwest::Url;
+
+use crate::utils::USER_AGENT_STR;
+
+/// The public song.link API allows 10 requests a minute without a key.
+const SONG_LINK_REQUESTS_PER_MINUTE: u32 = 10;
+
+#[derive(Debug, Clone)]
+pub struct MusicLinkServiceConfig {
+    /// Sent with every request to upstream services.
+    pub user_agent: String,
+    /// Longest a single upstream request may take.
+    pub request_timeout: Duration,
+    /// How long a link that resolved to no music is skipped without asking upstream.
+    pub negative_cache_ttl: Duration,
+    /// When not empty, only links on these domains (or their subdomains) are resolved.
+    pub allowed_domains: Vec<String>,
+    /// Links on these domains (or their subdomains) are never resolved.
+    pub denied_domains: Vec<String>,
+    pub song_link: SongLinkConfig,
+    /// Enables the Spotify Web API resolver after song.link when set.
+    pub spotify: Option<SpotifyConfig>,
+    /// Enables searching Deezer for platforms the resolvers did not find when set.
+    pub deezer: Option<DeezerConfig>,
+    pub matching: MatchingConfig,
+    pub cache: MusicLinkCacheConfig,
+}
+
+impl Default for MusicLinkServiceConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: USER_AGENT_STR.to_owned(),
+            request_timeout: Duration::from_secs(15),
+            negative_cache_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+            allowed_domains: vec![],
+            denied_domains: [
+                "x.com",
+                "github.com",
+                "reddit.com",
+                "twitter.com",
+                "gitlab.com",
+                "facebook.com",
+                "linkedin.com",
+                "instagram.com",
+                "wikipedia.org",
+                "stackoverflow.com",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            song_link: SongLinkConfig::default(),
+            spotify: None,
+            deezer: Some(DeezerConfig::default()),
+            matching: MatchingConfig::default(),
+            cache: MusicLinkCacheConfig::default(),
+        }
+    }
+}
+
+fn matches_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+impl MusicLinkServiceConfig {
+    /// Whether a link passes the domain allow and deny lists.
+    pub fn is_domain_allowed(&self, link: &str) -> bool {
+        let Some(host) = Url::parse(link)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+        else {
+            return false;
+        };
+        if self
+            .denied_domains
+            .iter()
+            .any(|domain| matches_domain(&host, domain))
+        {
+            return false;
+        }
+        self.allowed_domains.is_empty()
+            || self
+                .allowed_domains
+                .iter()
+                .any(|domain| matches_domain(&host, domain))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MusicLinkRefreshConfig {
+    /// Maximum number of music links re-resolved in a single run.
+    pub budget: u64,
+    /// Links resolved longer ago than this are always re-resolved.
+    pub stale_after: Duration,
+    /// Links that lost a platform they had, because its link went dead, are
+    /// re-resolved once they are older than this.
+    pub missing_platforms_after: Duration,
+    /// Only links someone interacted with this recently are re-resolved, so
+    /// forgotten links do not spend the upstream budget live requests need.
+    pub active_within: Duration,
+}
+
+impl Default for MusicLinkRefreshConfig {
+    fn default() -> Self {
+        Self {
+            budget: 10,
+            stale_after: Duration::from_secs(30 * 24 * 60 * 60),
+            missing_platforms_after: Duration::from_secs(7 * 24 * 60 * 60),
+            active_within: Duration::from_secs(90 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkAvailabilityConfig {
+    /// Maximum number of platform links checked in a single run.
+    pub budget: u64,
+    /// Links checked longer ago than this are checked again.
+    pub check_after: Duration,
+}
+
+impl Default for LinkAvailabilityConfig {
+    fn default() -> Self {
+        Self {
+            budget: 50,
+            check_after: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SongLinkConfig {
+    /// Url of the links endpoint, overridable to point at a stub server.
+    pub api_url: String,
+    pub upstream: UpstreamConfig,
+}
+
+impl Default for SongLinkConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://api.song.link/v1-alpha.1/links".to_owned(),
+            upstream: UpstreamConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpotifyConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Base url of the Web API, overridable to point at a stub server.
+    pub api_base_url: String,
+    /// Base url of the accounts service that issues access tokens.
+    pub accounts_base_url: String,
+    pub upstream: UpstreamConfig,
+}
+
+impl SpotifyConfig {
+    pub fn new(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            api_base_url: "https://api.spotify.com/v1".to_owned(),
+            accounts_base_url: "https://accounts.spotify.com".to_owned(),
+            upstream: UpstreamConfig::with_requests_per_minute(120),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeezerConfig {
+    /// Base url of the public API, overridable to point at a stub server.
+    pub api_base_url: String,
+    pub upstream: UpstreamConfig,
+}
+
+impl Default for DeezerConfig {
+    fn default() -> Self {
+        Self {
+            api_base_url: "https://api.deezer.com".to_owned(),
+            upstream: UpstreamConfig::with_requests_per_minute(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchingConfig {
+    /// Search candidates scoring below this are never stored.
+    pub min_confidence: u8,
+    /// Stored links scoring below this are left out of responses.
+    pub visible_confidence: u8,
+    /// Durations closer than this count as a full match; the score falls off
+    /// linearly until three times this difference.
+    pub duration_tolerance: Duration,
+}
+
+impl Default for MatchingConfig {
+    fn default() -> Self {
+        Self {
+            min_confidence: 50,
+            visible_confidence: 75,
+            duration_tolerance: Duration::from_secs(3),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MusicLinkCacheConfig {
+    /// Most resolved links kept in memory; the least recently used are evicted first.
+    pub capacity: usize,
+    /// How long a cached link is served before it is read from the database again.
+    pub ttl: Duration,
+    /// How often `last_interacted_at` updates for cache hits are written out.
+    pub touch_flush_interval: Duration,
+}
+
+impl Default for MusicLinkCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            ttl: Duration::from_secs(10 * 60),
+            touch_flush_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Rate limiting, retries and circuit breaking for calls to one upstream API.
+#[derive(Debug, Clone)]
+pub struct UpstreamConfig {
+    /// Sustained request rate; also the size of the burst allowed after idling.
+    pub requests_per_minute: u32,
+    /// Longest a request waits for a rate limiter token before giving up.
+    pub max_queue_wait: Duration,
+    /// Retries after the first attempt for rate limited or unavailable responses.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every following one.
+    pub base_backoff: Duration,
+    /// Retries are abandoned when upstream asks to wait longer than this.
+    pub max_backoff: Duration,
+    /// Consecutive failures after which the circuit opens.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a trial request is let through.
+    pub open_duration: Duration,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: SONG_LINK_REQUESTS_PER_MINUTE,
+            max_queue_wait: Duration::from_secs(30),
+            max_retries: 2,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(60),
+        }
+    }
+}
+
+impl UpstreamConfig {
+    /// The defaults with a different sustained request rate.
+    pub fn with_requests_per_minute(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+            ..Default::default()
+        }
+    }
+}
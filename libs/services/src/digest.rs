@@ -0,0 +1,308 @@
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use entities::{
+    prelude::{TelegramBotChannel, TelegramBotChannelSettings},
+    telegram_bot_channel_settings,
+};
+use sea_orm::{ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    stats::{
+        ShareCounts, SharerStats, TrackStats, chat_share_stats, most_divisive_tracks, top_sharers,
+        top_tracks,
+    },
+    utils::escape_html,
+};
+
+/// A digest is only posted this long after it was due, so an outage does not
+/// end with a burst of stale digests.
+const DIGEST_GRACE_PERIOD: Duration = Duration::hours(3);
+/// Period a digest looks back over.
+const DIGEST_PERIOD: Duration = Duration::days(7);
+const DIGEST_TOP_TRACKS: u64 = 3;
+
+/// When a chat wants its digest, in the chat's own timezone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestSchedule {
+    pub weekday: Weekday,
+    pub hour: u32,
+    pub timezone: Tz,
+}
+
+impl DigestSchedule {
+    /// Reads the schedule stored in a chat's settings, where the weekday runs
+    /// from 1 for Monday to 7 for Sunday.
+    pub fn from_settings(settings: &telegram_bot_channel_settings::Model) -> Option<Self> {
+        let weekday = u8::try_from(settings.digest_weekday).ok()?.checked_sub(1)?;
+        let hour = u32::try_from(settings.digest_hour)
+            .ok()
+            .filter(|h| *h < 24)?;
+        Some(Self {
+            weekday: Weekday::try_from(weekday).ok()?,
+            hour,
+            timezone: parse_timezone(&settings.timezone)?,
+        })
+    }
+
+    /// The latest time the digest was scheduled for, at or before `now`.
+    pub fn last_due(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = now.with_timezone(&self.timezone);
+        let days_back =
+            (local.weekday().num_days_from_monday() + 7 - self.weekday.num_days_from_monday()) % 7;
+        let time = NaiveTime::from_hms_opt(self.hour, 0, 0)?;
+        let due_on = |days_back: u32| {
+            let date = local.date_naive() - Duration::days(days_back.into());
+            let naive = date.and_time(time);
+            // Hours skipped by a daylight saving change fall through to the next one.
+            self.timezone
+                .from_local_datetime(&naive)
+                .earliest()
+                .or_else(|| {
+                    self.timezone
+                        .from_local_datetime(&(naive + Duration::hours(1)))
+                        .earliest()
+                })
+                .map(|due| due.with_timezone(&Utc))
+        };
+        match due_on(days_back)? {
+            due if due <= now => Some(due),
+            _ => due_on(days_back + 7),
+        }
+    }
+}
+
+/// Reads an IANA timezone name such as `Europe/Berlin`.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// Whether a chat's digest should be posted at `now`.
+pub fn is_digest_due(settings: &telegram_bot_channel_settings::Model, now: DateTime<Utc>) -> bool {
+    if !settings.digest_enabled {
+        return false;
+    }
+    let Some(due) = DigestSchedule::from_settings(settings).and_then(|s| s.last_due(now)) else {
+        return false;
+    };
+    now - due < DIGEST_GRACE_PERIOD && settings.digest_last_sent_at.is_none_or(|sent| sent < due)
+}
+
+/// What a chat shared over the past week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatDigest {
+    pub counts: ShareCounts,
+    pub top_tracks: Vec<TrackStats>,
+    pub most_active_sharer: Option<SharerStats>,
+    pub most_divisive_track: Option<TrackStats>,
+}
+
+/// The digest of what a chat shared over the week before `now`.
+pub async fn chat_digest(
+    telegram_channel_id: i64,
+    now: DateTime<Utc>,
+    db: &DatabaseConnection,
+) -> Result<ChatDigest> {
+    let since = Some(now - DIGEST_PERIOD);
+    let counts = chat_share_stats(telegram_channel_id, since, db).await?;
+    let top_tracks = top_tracks(telegram_channel_id, since, DIGEST_TOP_TRACKS, db).await?;
+    let most_active_sharer = top_sharers(telegram_channel_id, since, 1, db).await?.pop();
+    let most_divisive_track = most_divisive_tracks(telegram_channel_id, since, 1, db)
+        .await?
+        .pop();
+    Ok(ChatDigest {
+        counts,
+        top_tracks,
+        most_active_sharer,
+        most_divisive_track,
+    })
+}
+
+fn track_name(track: &TrackStats) -> String {
+    let title = track.title.as_deref().unwrap_or("Unknown title");
+    let name = match track.artists.is_empty() {
+        true => title.to_owned(),
+        false => format!("{} – {}", track.artists.join(", "), title),
+    };
+    escape_html(&name)
+}
+
+/// Formats a digest as an HTML message, naming the most active sharer with
+/// `sharer_name` when they could be looked up.
+pub fn format_chat_digest(digest: &ChatDigest, sharer_name: Option<&str>) -> String {
+    let counts = &digest.counts;
+    let mut text = format!(
+        "<b>Weekly digest</b>\n{} links shared, {} reactions received.",
+        counts.shares, counts.reactions
+    );
+    if !digest.top_tracks.is_empty() {
+        text.push_str("\n\n<b>Top tracks</b>");
+        for (idx, track) in digest.top_tracks.iter().enumerate() {
+            text.push_str(&format!(
+                "\n{}. {} – {} positive reactions",
+                idx + 1,
+                track_name(track),
+                track.counts.positive_reactions
+            ));
+        }
+    }
+    if let Some(sharer) = &digest.most_active_sharer {
+        text.push_str(&format!(
+            "\n\n<b>Most active sharer</b>\n{} with {} links",
+            escape_html(sharer_name.unwrap_or("Someone who left")),
+            sharer.counts.shares
+        ));
+    }
+    if let Some(track) = &digest.most_divisive_track {
+        text.push_str(&format!(
+            "\n\n<b>Most divisive</b>\n{} – {} loved it, {} did not",
+            track_name(track),
+            track.counts.positive_reactions,
+            track.counts.negative_reactions
+        ));
+    }
+    text
+}
+
+/// A chat whose weekly digest is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DueDigest {
+    pub telegram_channel_id: i64,
+    /// The chat's settings, where the digest is marked as sent.
+    pub settings_id: Uuid,
+}
+
+/// Chats that opted into the weekly digest and are due for it at `now`.
+///
+/// A failed chat stays due, so it is retried on the next run until its grace
+/// period runs out.
+pub async fn due_digests(now: DateTime<Utc>, db: &DatabaseConnection) -> Result<Vec<DueDigest>> {
+    let opted_in = TelegramBotChannelSettings::find()
+        .filter(telegram_bot_channel_settings::Column::DigestEnabled.eq(true))
+        .find_also_related(TelegramBotChannel)
+        .all(db)
+        .await?;
+    let due = opted_in
+        .into_iter()
+        .filter(|(settings, _)| is_digest_due(settings, now))
+        .filter_map(|(settings, channel)| {
+            Some(DueDigest {
+                telegram_channel_id: channel?.telegram_channel_id,
+                settings_id: settings.id,
+            })
+        })
+        .collect();
+    Ok(due)
+}
+
+/// Records that a chat's digest was handled at `now`, also when it had no
+/// shares to report.
+pub async fn mark_digest_sent(
+    settings_id: Uuid,
+    now: DateTime<Utc>,
+    db: &DatabaseConnection,
+) -> Result<()> {
+    TelegramBotChannelSettings::update(telegram_bot_channel_settings::ActiveModel {
+        id: ActiveValue::Unchanged(settings_id),
+        digest_last_sent_at: ActiveValue::Set(Some(now)),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use entities::telegram_bot_channel_settings::{DuplicateShareMode, ReplyLanguage};
+
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedule(weekday: Weekday, hour: u32, timezone: Tz) -> DigestSchedule {
+        DigestSchedule {
+            weekday,
+            hour,
+            timezone,
+        }
+    }
+
+    /// Settings for a digest due on Mondays at 09:00 UTC.
+    fn monday_digest() -> telegram_bot_channel_settings::Model {
+        telegram_bot_channel_settings::Model {
+            id: Uuid::nil(),
+            created_at: utc(2025, 1, 1, 0, 0),
+            telegram_bot_channel_id: Uuid::nil(),
+            delete_original: false,
+            failure_reaction_enabled: false,
+            failure_reaction_emoji: String::new(),
+            platforms: vec![],
+            reply_language: ReplyLanguage::En,
+            duplicate_share_mode: DuplicateShareMode::Annotate,
+            digest_enabled: true,
+            digest_weekday: 1,
+            digest_hour: 9,
+            timezone: "UTC".to_owned(),
+            digest_last_sent_at: None,
+            wrapped_enabled: false,
+        }
+    }
+
+    #[test]
+    fn hour_skipped_by_spring_forward_falls_through_to_the_next() {
+        // Berlin skips from 02:00 to 03:00 on 2025-03-30, a Sunday.
+        let schedule = schedule(Weekday::Sun, 2, Tz::Europe__Berlin);
+        let due = schedule.last_due(utc(2025, 3, 30, 1, 30));
+        assert_eq!(due, Some(utc(2025, 3, 30, 1, 0)));
+    }
+
+    #[test]
+    fn due_weekday_later_today_falls_back_a_week() {
+        // 12:00 in Berlin on a Monday, with the digest due at 18:00.
+        let schedule = schedule(Weekday::Mon, 18, Tz::Europe__Berlin);
+        let due = schedule.last_due(utc(2025, 6, 2, 10, 0));
+        assert_eq!(due, Some(utc(2025, 5, 26, 16, 0)));
+    }
+
+    #[test]
+    fn due_weekday_earlier_in_the_week_wraps_back() {
+        let schedule = schedule(Weekday::Fri, 9, Tz::UTC);
+        let due = schedule.last_due(utc(2025, 6, 3, 8, 0));
+        assert_eq!(due, Some(utc(2025, 5, 30, 9, 0)));
+    }
+
+    #[test]
+    fn due_only_within_the_grace_period() {
+        let settings = monday_digest();
+        assert!(is_digest_due(&settings, utc(2025, 6, 2, 9, 0)));
+        assert!(is_digest_due(&settings, utc(2025, 6, 2, 11, 59)));
+        assert!(!is_digest_due(&settings, utc(2025, 6, 2, 12, 0)));
+        assert!(!is_digest_due(&settings, utc(2025, 6, 2, 8, 59)));
+    }
+
+    #[test]
+    fn not_due_once_sent() {
+        let mut settings = monday_digest();
+        settings.digest_last_sent_at = Some(utc(2025, 6, 2, 9, 5));
+        assert!(!is_digest_due(&settings, utc(2025, 6, 2, 10, 0)));
+
+        settings.digest_last_sent_at = Some(utc(2025, 5, 26, 9, 5));
+        assert!(is_digest_due(&settings, utc(2025, 6, 2, 10, 0)));
+    }
+
+    #[test]
+    fn not_due_when_disabled_or_unschedulable() {
+        let mut settings = monday_digest();
+        settings.digest_enabled = false;
+        assert!(!is_digest_due(&settings, utc(2025, 6, 2, 9, 0)));
+
+        let mut settings = monday_digest();
+        settings.timezone = "Mars/Olympus_Mons".to_owned();
+        assert!(!is_digest_due(&settings, utc(2025, 6, 2, 9, 0)));
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use sea_orm::DbErr;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MusicLinkError {
+    #[error("upstream rate limit reached")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("upstream unavailable: {0}")]
+    UpstreamUnavailable(String),
+    #[error("unsupported url: {0}")]
+    UnsupportedUrl(String),
+    #[error("no music found for the link")]
+    NotFound,
+    #[error("database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl MusicLinkError {
+    /// A stable, machine readable identifier for the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MusicLinkError::RateLimited { .. } => "RATE_LIMITED",
+            MusicLinkError::UpstreamUnavailable(_) => "UPSTREAM_UNAVAILABLE",
+            MusicLinkError::UnsupportedUrl(_) => "UNSUPPORTED_URL",
+            MusicLinkError::NotFound => "NOT_FOUND",
+            MusicLinkError::Database(_) => "DATABASE",
+        }
+    }
+
+    /// Whether the same request could succeed if it is retried later.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            MusicLinkError::RateLimited { .. } | MusicLinkError::UpstreamUnavailable(_)
+        )
+    }
+}
+
+impl From<reqwest::Error> for MusicLinkError {
+    fn from(e: reqwest::Error) -> Self {
+        MusicLinkError::UpstreamUnavailable(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, MusicLinkError>;
@@ -1,72 +1,375 @@
-use anyhow::Result;
+use std::{collections::HashMap, sync::Arc};
+
 use chrono::Utc;
-use entities::{music_link, prelude::MusicLink};
-use reqwest::{Client, Url};
-use rust_iso3166::{US, from_alpha2};
+use entities::{
+    music_link, music_link_negative_cache, music_link_platform,
+    prelude::{MusicLink, MusicLinkNegativeCache, MusicLinkPlatform},
+};
+use reqwest::{
+    Client,
+    header::{HeaderValue, USER_AGENT},
+};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, QueryFilter, prelude::Expr,
-    sea_query::PgFunc,
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, Iterable,
+    ModelTrait, QueryFilter,
+    prelude::Expr,
+    sea_query::{OnConflict, PgFunc},
 };
-use strum::IntoEnumIterator;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
 use uuid::Uuid;
 
+mod availability;
+mod cache;
+mod canonical;
+mod config;
+mod digest;
+mod error;
+mod matching;
 mod models;
+mod refresh;
+mod resolvers;
+mod settings;
+mod stats;
+mod storefront;
+#[cfg(test)]
+mod test_server;
+mod upstream;
 mod utils;
+mod wrapped;
+
+pub use availability::LinkAvailabilitySummary;
+use cache::{CachedMusicLink, MusicLinkCache};
+pub use canonical::{
+    canonicalize_url, detect_entity_kind, detect_platform, expand_and_canonicalize_url,
+};
+pub use config::{
+    DeezerConfig, LinkAvailabilityConfig, MatchingConfig, MusicLinkCacheConfig,
+    MusicLinkRefreshConfig, MusicLinkServiceConfig, SongLinkConfig, SpotifyConfig, UpstreamConfig,
+};
+pub use digest::{
+    ChatDigest, DigestSchedule, DueDigest, chat_digest, due_digests, format_chat_digest,
+    is_digest_due, mark_digest_sent, parse_timezone,
+};
+pub use error::{MusicLinkError, Result};
+pub use matching::{MusicLinkMatcher, score_candidate, score_text_candidate};
+pub use models::{
+    EXACT_MATCH_CONFIDENCE, MusicEntityKind, MusicLinkData, MusicLinkInput, MusicLinkResponse,
+    MusicMetadata, MusicPlatform, MusicSearchInput, PlatformLink,
+};
+pub use refresh::MusicLinkRefreshSummary;
+pub use resolvers::{
+    DeezerResolver, MusicResolver, MusicSearchResolver, ResolvedMusicLinks, SearchCandidate,
+    SongLinkResolver, SpotifyResolver,
+};
+pub use settings::{MusicLinkSettings, PartialMusicLinkSettings};
+pub use stats::{
+    ArtistStats, ShareCounts, ShareStats, ShareStreak, SharerStats, TrackStats,
+    best_received_sharers, chat_share_stats, most_divisive_tracks, top_sharers, top_tracks,
+    user_share_stats,
+};
+use storefront::localize_platform_links;
+pub use storefront::{
+    DEFAULT_COUNTRY, country_from_language_code, localize_link, normalize_country_code,
+};
+pub use upstream::{UpstreamGuard, UpstreamStats};
+use utils::{USER_AGENT_STR, get_base_http_client};
+pub use wrapped::{
+    WrappedChat, WrappedPeriod, WrappedRecap, format_wrapped, render_wrapped_card, wrapped_chats,
+    wrapped_recap,
+};
+
+/// Builds the response for a stored music link, listing every platform in
+/// `MusicPlatform` order whether or not a link was found for it. Links matched
+/// with less than `visible_confidence` are reported as not found.
+fn build_music_link_response(
+    music_link: music_link::Model,
+    mut platform_links: HashMap<MusicPlatform, PlatformLink>,
+    visible_confidence: u8,
+) -> MusicLinkResponse {
+    let collected_links: Vec<MusicLinkData> = MusicPlatform::iter()
+        .map(|platform| match platform_links.remove(&platform) {
+            Some(found) if found.confidence >= visible_confidence => MusicLinkData {
+                platform,
+                link: Some(found.link),
+                confidence: found.confidence,
+            },
+            _ => MusicLinkData {
+                platform,
+                link: None,
+                confidence: 0,
+            },
+        })
+        .collect();
+    let found = collected_links
+        .iter()
+        .filter(|link| link.link.is_some())
+        .count() as u8;
+    let metadata = MusicMetadata {
+        upc: music_link.upc,
+        isrc: music_link.isrc,
+        album: music_link.album,
+        title: music_link.title,
+        artists: music_link.artists,
+        artwork_url: music_link.artwork_url,
+        duration_ms: music_link.duration_ms,
+    };
+    MusicLinkResponse {
+        found,
+        kind: music_link.kind,
+        metadata,
+        collected_links,
+        id: music_link.id,
+    }
+}
 
-use models::providers::{SongLinkPlatform, SongLinkResponse};
-pub use models::{MusicLinkData, MusicLinkInput, MusicLinkResponse, MusicPlatform};
-use utils::{SONG_LINK_API_URL, get_base_http_client};
+/// Cached responses differ per storefront, so the country is part of the key.
+fn cache_key(country: &str, link: &str) -> String {
+    format!("{country}:{link}")
+}
+
+fn http_client_for(config: &MusicLinkServiceConfig) -> Client {
+    let user_agent = HeaderValue::from_str(&config.user_agent)
+        .unwrap_or_else(|_| HeaderValue::from_static(USER_AGENT_STR));
+    get_base_http_client(Some(vec![(USER_AGENT, user_agent)]), config.request_timeout)
+}
 
 pub struct MusicLinkService {
     client: Client,
+    cache: MusicLinkCache,
+    matcher: MusicLinkMatcher,
+    config: MusicLinkServiceConfig,
+    resolvers: Vec<Arc<dyn MusicResolver>>,
+    /// Guards of the default resolvers, kept to report their stats.
+    upstreams: Vec<Arc<UpstreamGuard>>,
+}
+
+impl Default for MusicLinkService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MusicLinkService {
-    pub async fn new() -> Self {
-        let client = get_base_http_client(None);
-        Self { client }
+    pub fn new() -> Self {
+        Self::from_config(MusicLinkServiceConfig::default())
+    }
+
+    /// Creates a service with the default resolver chain for `config`: song.link,
+    /// followed by Spotify when it is configured. Deezer and Spotify are searched
+    /// for platforms the chain did not find, when configured.
+    ///
+    /// Every resolver shares the service's HTTP client and its connection pool.
+    pub fn from_config(config: MusicLinkServiceConfig) -> Self {
+        let client = http_client_for(&config);
+        let song_link = SongLinkResolver::new(client.clone(), config.song_link.clone());
+        let mut upstreams = vec![song_link.guard()];
+        let mut resolvers: Vec<Arc<dyn MusicResolver>> = vec![Arc::new(song_link)];
+        let mut searchers: Vec<Arc<dyn MusicSearchResolver>> = vec![];
+        if let Some(deezer) = &config.deezer {
+            let deezer = DeezerResolver::new(client.clone(), deezer.clone());
+            upstreams.push(deezer.guard());
+            searchers.push(Arc::new(deezer));
+        }
+        if let Some(spotify) = &config.spotify {
+            let spotify = Arc::new(SpotifyResolver::new(client.clone(), spotify.clone()));
+            upstreams.push(spotify.guard());
+            resolvers.push(spotify.clone());
+            searchers.push(spotify);
+        }
+        let mut service = Self::with_client(client, config, resolvers, searchers);
+        service.upstreams = upstreams;
+        service
+    }
+
+    /// Creates a service that queries `resolvers` in order, falling back to the
+    /// next one for any platform the previous resolvers did not find, and then
+    /// to `searchers` for the platforms still missing.
+    pub fn with_config(
+        config: MusicLinkServiceConfig,
+        resolvers: Vec<Arc<dyn MusicResolver>>,
+        searchers: Vec<Arc<dyn MusicSearchResolver>>,
+    ) -> Self {
+        let client = http_client_for(&config);
+        Self::with_client(client, config, resolvers, searchers)
+    }
+
+    fn with_client(
+        client: Client,
+        config: MusicLinkServiceConfig,
+        resolvers: Vec<Arc<dyn MusicResolver>>,
+        searchers: Vec<Arc<dyn MusicSearchResolver>>,
+    ) -> Self {
+        let matcher = MusicLinkMatcher::new(config.matching.clone(), searchers);
+        let cache = MusicLinkCache::new(&config.cache);
+        Self {
+            cache,
+            client,
+            config,
+            matcher,
+            resolvers,
+            upstreams: vec![],
+        }
+    }
+
+    /// Request, failure and circuit breaker counters for every upstream API
+    /// the default resolvers call.
+    pub fn upstream_stats(&self) -> Vec<UpstreamStats> {
+        self.upstreams.iter().map(|guard| guard.stats()).collect()
+    }
+
+    /// Logs `upstream_stats`, so limits can be tuned from the logs.
+    pub fn log_upstream_stats(&self) {
+        for stats in self.upstream_stats() {
+            tracing::info!("Upstream {} stats: {:?}", stats.name, stats);
+        }
+    }
+
+    async fn is_negatively_cached(&self, link: &str, db: &DatabaseConnection) -> Result<bool> {
+        let cached = MusicLinkNegativeCache::find()
+            .filter(music_link_negative_cache::Column::Link.eq(link))
+            .filter(music_link_negative_cache::Column::ExpiresAt.gt(Utc::now()))
+            .one(db)
+            .await?;
+        Ok(cached.is_some())
+    }
+
+    async fn save_negative_cache_to_db(
+        &self,
+        link: &str,
+        reason: &str,
+        db: &DatabaseConnection,
+    ) -> Result<()> {
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.config.negative_cache_ttl).unwrap_or_default();
+        let to_insert = music_link_negative_cache::ActiveModel {
+            link: ActiveValue::Set(link.to_owned()),
+            reason: ActiveValue::Set(reason.to_owned()),
+            expires_at: ActiveValue::Set(expires_at),
+            ..Default::default()
+        };
+        MusicLinkNegativeCache::insert(to_insert)
+            .on_conflict(
+                OnConflict::column(music_link_negative_cache::Column::Link)
+                    .update_columns([
+                        music_link_negative_cache::Column::Reason,
+                        music_link_negative_cache::Column::ExpiresAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
     }
 
     async fn get_music_link_from_db(
         &self,
-        link: &String,
+        link: &str,
         db: &DatabaseConnection,
     ) -> Result<Option<music_link::Model>> {
-        let music_link = MusicLink::find()
-            .filter(Expr::val(link).eq(PgFunc::any(Expr::col(music_link::Column::AllLinks))))
+        let by_platform_link = MusicLinkPlatform::find()
+            .filter(music_link_platform::Column::Link.eq(link))
+            .find_also_related(MusicLink)
             .one(db)
-            .await?;
+            .await?
+            .and_then(|(_, music_link)| music_link);
+        let music_link = match by_platform_link {
+            Some(music_link) => Some(music_link),
+            None => {
+                MusicLink::find()
+                    .filter(
+                        Expr::val(link)
+                            .eq(PgFunc::any(Expr::col(music_link::Column::EquivalentLinks))),
+                    )
+                    .one(db)
+                    .await?
+            }
+        };
         if let Some(music_link) = &music_link {
-            let mut active: music_link::ActiveModel = music_link.clone().into();
-            active.last_interacted_at = ActiveValue::Set(Utc::now());
-            active.update(db).await?;
+            self.cache.touch(music_link.id);
         }
         Ok(music_link)
     }
 
+    async fn write_interactions(&self, ids: Vec<Uuid>, db: &DatabaseConnection) -> Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let result = MusicLink::update_many()
+            .col_expr(
+                music_link::Column::LastInteractedAt,
+                Expr::value(Utc::now()),
+            )
+            .filter(music_link::Column::Id.is_in(ids))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Writes every pending `last_interacted_at` update, e.g. before shutting down.
+    pub async fn flush_interactions(&self, db: &DatabaseConnection) -> Result<u64> {
+        let ids = self.cache.take_touches();
+        self.write_interactions(ids, db).await
+    }
+
+    /// Writes the batched `last_interacted_at` updates every
+    /// `touch_flush_interval` until the returned task is aborted. Updates made
+    /// after the last tick still need a `flush_interactions` on shutdown.
+    pub fn spawn_interaction_flusher(self: &Arc<Self>, db: DatabaseConnection) -> JoinHandle<()> {
+        let service = self.clone();
+        let period = self.config.cache.touch_flush_interval;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately, with nothing to write yet.
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                // A failed write only loses those updates, so the task keeps going.
+                if let Err(e) = service.flush_interactions(&db).await {
+                    tracing::warn!("Failed to write music link interactions: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn get_platform_links_from_db(
+        &self,
+        music_link: &music_link::Model,
+        db: &DatabaseConnection,
+    ) -> Result<HashMap<MusicPlatform, PlatformLink>> {
+        let platform_links = music_link
+            .find_related(MusicLinkPlatform)
+            .filter(music_link_platform::Column::IsAvailable.eq(true))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|platform_link| {
+                let link = PlatformLink {
+                    link: platform_link.link,
+                    confidence: u8::try_from(platform_link.confidence).unwrap_or_default(),
+                };
+                (platform_link.platform, link)
+            })
+            .collect();
+        Ok(platform_links)
+    }
+
     async fn save_music_link_to_db(
         &self,
         original_link: &str,
+        country: &str,
         db: &DatabaseConnection,
-        links: &[MusicLinkData],
-    ) -> Result<Uuid> {
-        let spotify_link = links
-            .iter()
-            .find(|link| link.platform == MusicPlatform::Spotify)
-            .and_then(|link| link.link.clone());
-        let apple_music_link = links
-            .iter()
-            .find(|link| link.platform == MusicPlatform::AppleMusic)
-            .and_then(|link| link.link.clone());
-        let youtube_music_link = links
-            .iter()
-            .find(|link| link.platform == MusicPlatform::YoutubeMusic)
-            .and_then(|link| link.link.clone());
-        for link in [&spotify_link, &apple_music_link, &youtube_music_link]
-            .into_iter()
-            .flatten()
-        {
-            let already = self.get_music_link_from_db(link, db).await?;
+        kind: MusicEntityKind,
+        links: &HashMap<MusicPlatform, PlatformLink>,
+        metadata: &MusicMetadata,
+    ) -> Result<music_link::Model> {
+        // Best guesses could point at a different song, so only exact links are
+        // trusted to identify an existing music link.
+        let exact_links = links
+            .values()
+            .filter(|link| link.confidence == EXACT_MATCH_CONFIDENCE);
+        for link in exact_links {
+            let already = self.get_music_link_from_db(&link.link, db).await?;
             if let Some(already) = already {
                 let mut new_links = already.equivalent_links.clone();
                 new_links.push(original_link.to_owned());
@@ -74,18 +377,136 @@ impl MusicLinkService {
                 active.equivalent_links = ActiveValue::Set(new_links);
                 active.last_interacted_at = ActiveValue::Set(Utc::now());
                 let updated = active.update(db).await?;
-                return Ok(updated.id);
+                return Ok(updated);
             }
         }
         let to_insert = music_link::ActiveModel {
-            spotify_link: ActiveValue::Set(spotify_link),
-            apple_music_link: ActiveValue::Set(apple_music_link),
-            youtube_music_link: ActiveValue::Set(youtube_music_link),
+            kind: ActiveValue::Set(kind),
+            upc: ActiveValue::Set(metadata.upc.clone()),
+            isrc: ActiveValue::Set(metadata.isrc.clone()),
+            album: ActiveValue::Set(metadata.album.clone()),
+            title: ActiveValue::Set(metadata.title.clone()),
+            artists: ActiveValue::Set(metadata.artists.clone()),
+            country: ActiveValue::Set(country.to_owned()),
+            artwork_url: ActiveValue::Set(metadata.artwork_url.clone()),
+            duration_ms: ActiveValue::Set(metadata.duration_ms),
             equivalent_links: ActiveValue::Set(vec![original_link.to_owned()]),
             ..Default::default()
         };
         let inserted = to_insert.insert(db).await?;
-        Ok(inserted.id)
+        let platform_links: Vec<_> = links
+            .iter()
+            .map(|(platform, link)| music_link_platform::ActiveModel {
+                link: ActiveValue::Set(link.link.clone()),
+                music_link_id: ActiveValue::Set(inserted.id),
+                platform: ActiveValue::Set(*platform),
+                confidence: ActiveValue::Set(i16::from(link.confidence)),
+                ..Default::default()
+            })
+            .collect();
+        if !platform_links.is_empty() {
+            // A best guess may already belong to another music link, in which
+            // case it is left out rather than failing the whole insert.
+            MusicLinkPlatform::insert_many(platform_links)
+                .on_conflict(OnConflict::new().do_nothing().to_owned())
+                .exec_without_returning(db)
+                .await?;
+        }
+        Ok(inserted)
+    }
+
+    /// Caches the links localized for `country`, since storefront links differ
+    /// between countries, and returns them.
+    fn cache_music_link(
+        &self,
+        link: &str,
+        country: &str,
+        music_link: &music_link::Model,
+        platform_links: &HashMap<MusicPlatform, PlatformLink>,
+    ) -> HashMap<MusicPlatform, PlatformLink> {
+        let platform_links = localize_platform_links(platform_links, country);
+        let cached = CachedMusicLink {
+            music_link: music_link.clone(),
+            platform_links: platform_links.clone(),
+        };
+        self.cache.insert(cache_key(country, link), cached);
+        platform_links
+    }
+
+    async fn resolve_with_chain(&self, input: &MusicLinkInput) -> Result<ResolvedMusicLinks> {
+        let mut merged = ResolvedMusicLinks::default();
+        let mut last_error = None;
+        let mut any_succeeded = false;
+        for resolver in &self.resolvers {
+            if MusicPlatform::iter().all(|platform| merged.links.contains_key(&platform)) {
+                break;
+            }
+            tracing::debug!("Querying resolver: {}", resolver.name());
+            match resolver.resolve(input, &merged).await {
+                Ok(resolved) => {
+                    any_succeeded = true;
+                    for (platform, link) in resolved.links {
+                        merged.links.entry(platform).or_insert(link);
+                    }
+                    merged.kind = merged.kind.or(resolved.kind);
+                    merged.metadata.fill_missing_from(resolved.metadata);
+                }
+                Err(e) => {
+                    tracing::warn!("Resolver {} failed: {}", resolver.name(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        if !any_succeeded && let Some(e) = last_error {
+            return Err(e);
+        }
+        Ok(merged)
+    }
+
+    /// Looks a canonical link up in the cache, then in the database.
+    async fn find_known_music_link(
+        &self,
+        link: &str,
+        user_country: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<MusicLinkResponse>> {
+        if let Some(cached) = self.cache.get(&cache_key(user_country, link)) {
+            tracing::debug!("Found music link in cache: {}", cached.music_link.id);
+            self.cache.touch(cached.music_link.id);
+            return Ok(Some(build_music_link_response(
+                cached.music_link,
+                cached.platform_links,
+                self.config.matching.visible_confidence,
+            )));
+        }
+        let Some(music_link) = self.get_music_link_from_db(link, db).await? else {
+            return Ok(None);
+        };
+        tracing::debug!("Found music link in db: {:?}", music_link);
+        let platform_links = self.get_platform_links_from_db(&music_link, db).await?;
+        let platform_links =
+            self.cache_music_link(link, user_country, &music_link, &platform_links);
+        Ok(Some(build_music_link_response(
+            music_link,
+            platform_links,
+            self.config.matching.visible_confidence,
+        )))
+    }
+
+    /// Looks a link up among the music links resolved before, without
+    /// expanding it or asking any upstream, for callers that have to answer
+    /// right away. Returns `None` for links that were never resolved.
+    pub async fn find_resolved_music_link(
+        &self,
+        input: MusicLinkInput,
+        db: &DatabaseConnection,
+    ) -> Result<Option<MusicLinkResponse>> {
+        let link = canonicalize_url(&input.link);
+        let user_country = normalize_country_code(&input.user_country).unwrap_or(DEFAULT_COUNTRY);
+        if !self.config.is_domain_allowed(&link) {
+            return Err(MusicLinkError::UnsupportedUrl(link));
+        }
+        self.find_known_music_link(&link, user_country, db).await
     }
 
     pub async fn resolve_music_link(
@@ -95,89 +516,272 @@ impl MusicLinkService {
     ) -> Result<MusicLinkResponse> {
         tracing::debug!("Received link: {:?}", input);
 
-        let music_link = self.get_music_link_from_db(&input.link, db).await?;
-        if let Some(music_link) = music_link {
-            tracing::debug!("Found music link in db: {:?}", music_link);
-            let mut found = 0;
-            let mut collected_links = vec![];
-            if let Some(spotify_link) = music_link.spotify_link {
-                found += 1;
-                collected_links.push(MusicLinkData {
-                    link: Some(spotify_link),
-                    platform: MusicPlatform::Spotify,
-                });
-                if let Some(apple_music_link) = music_link.apple_music_link {
-                    found += 1;
-                    collected_links.push(MusicLinkData {
-                        link: Some(apple_music_link),
-                        platform: MusicPlatform::AppleMusic,
-                    });
-                }
-                if let Some(youtube_music_link) = music_link.youtube_music_link {
-                    found += 1;
-                    collected_links.push(MusicLinkData {
-                        link: Some(youtube_music_link),
-                        platform: MusicPlatform::YoutubeMusic,
-                    });
-                }
-            }
-            return Ok(MusicLinkResponse {
-                found,
-                collected_links,
-                id: music_link.id,
-            });
-        }
-
-        let user_country = from_alpha2(input.user_country.as_str()).unwrap_or(US);
-
-        let url = Url::parse_with_params(
-            SONG_LINK_API_URL,
-            &[
-                ("songIfSingle", "true"),
-                ("url", input.link.as_str()),
-                ("userCountry", user_country.alpha2),
-            ],
-        )?;
-        let response = self
-            .client
-            .get(url)
-            .send()
+        let link = expand_and_canonicalize_url(&self.client, &input.link).await;
+        tracing::debug!("Canonical link: {}", link);
+        let user_country = normalize_country_code(&input.user_country).unwrap_or(DEFAULT_COUNTRY);
+        let input = MusicLinkInput {
+            link,
+            user_country: user_country.to_owned(),
+        };
+
+        if !self.config.is_domain_allowed(&input.link) {
+            tracing::debug!("Domain not allowed for link: {}", input.link);
+            return Err(MusicLinkError::UnsupportedUrl(input.link));
+        }
+
+        if let Some(response) = self
+            .find_known_music_link(&input.link, user_country, db)
             .await?
-            .json::<SongLinkResponse>()
-            .await
-            .ok();
-
-        let mut found = 0;
-        let collected_links: Vec<MusicLinkData> = MusicPlatform::iter()
-            .map(|platform| {
-                let sl_platform = match platform {
-                    MusicPlatform::Spotify => SongLinkPlatform::Spotify,
-                    MusicPlatform::AppleMusic => SongLinkPlatform::AppleMusic,
-                    MusicPlatform::YoutubeMusic => SongLinkPlatform::YoutubeMusic,
-                };
-                let link = response.as_ref().and_then(|resp| {
-                    resp.links_by_platform
-                        .get(&sl_platform)
-                        .map(|link| link.url.clone())
-                });
-                if link.is_some() {
-                    found += 1;
-                }
-                MusicLinkData { link, platform }
-            })
-            .collect();
+        {
+            return Ok(response);
+        }
 
-        let id = self
-            .save_music_link_to_db(&input.link, db, &collected_links)
-            .await?;
+        if self.is_negatively_cached(&input.link, db).await? {
+            tracing::debug!("Link is negatively cached: {}", input.link);
+            return Err(MusicLinkError::NotFound);
+        }
 
-        let response = MusicLinkResponse {
-            id,
-            found,
-            collected_links,
+        let detected_kind = detect_entity_kind(&input.link);
+        let mut resolved = match self.resolve_with_chain(&input).await {
+            Err(e @ (MusicLinkError::NotFound | MusicLinkError::UnsupportedUrl(_)))
+                if detected_kind.is_none() =>
+            {
+                self.save_negative_cache_to_db(&input.link, e.code(), db)
+                    .await?;
+                return Err(e);
+            }
+            Err(MusicLinkError::NotFound | MusicLinkError::UnsupportedUrl(_)) => {
+                ResolvedMusicLinks::default()
+            }
+            result => result?,
         };
+        // Links whose kind is obvious from the url are known to be music, so they
+        // are kept even when no resolver could match them on other platforms.
+        if detected_kind.is_some()
+            && let Some(platform) = detect_platform(&input.link)
+        {
+            resolved
+                .links
+                .entry(platform)
+                .or_insert_with(|| input.link.clone());
+        }
+        if resolved.links.is_empty() {
+            self.save_negative_cache_to_db(&input.link, MusicLinkError::NotFound.code(), db)
+                .await?;
+            return Err(MusicLinkError::NotFound);
+        }
+        let kind = resolved.kind.or(detected_kind).unwrap_or_default();
+
+        let mut links: HashMap<_, _> = resolved
+            .links
+            .into_iter()
+            .map(|(platform, link)| (platform, PlatformLink::exact(canonicalize_url(&link))))
+            .collect();
+        let matched = self
+            .matcher
+            .find_matches(kind, &resolved.metadata, &links)
+            .await;
+        links.extend(matched);
+        let music_link = self
+            .save_music_link_to_db(
+                &input.link,
+                user_country,
+                db,
+                kind,
+                &links,
+                &resolved.metadata,
+            )
+            .await?;
+        let platform_links = self.get_platform_links_from_db(&music_link, db).await?;
+        let platform_links =
+            self.cache_music_link(&input.link, user_country, &music_link, &platform_links);
+        let response = build_music_link_response(
+            music_link,
+            platform_links,
+            self.config.matching.visible_confidence,
+        );
 
         tracing::debug!("Returning response {:?}", response);
         Ok(response)
     }
+
+    /// Looks a song up by free text such as "artist - title" and resolves the
+    /// best match across platforms like a shared link.
+    pub async fn search(
+        &self,
+        input: MusicSearchInput,
+        db: &DatabaseConnection,
+    ) -> Result<MusicLinkResponse> {
+        tracing::debug!("Received search: {:?}", input);
+        let query = input.query.trim();
+        if query.is_empty() {
+            return Err(MusicLinkError::NotFound);
+        }
+        let Some((candidate, confidence)) = self.matcher.search_text(query).await? else {
+            tracing::debug!("No confident match for search: {}", query);
+            return Err(MusicLinkError::NotFound);
+        };
+        tracing::debug!(
+            "Search matched {} with confidence {}",
+            candidate.link,
+            confidence
+        );
+        let input = MusicLinkInput {
+            link: candidate.link,
+            user_country: input.user_country,
+        };
+        self.resolve_music_link(input, db).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn stored_music_link() -> music_link::Model {
+        music_link::Model {
+            id: Uuid::from_u128(1),
+            kind: MusicEntityKind::Song,
+            upc: None,
+            isrc: Some("USUM71703861".to_owned()),
+            album: None,
+            title: Some("Title".to_owned()),
+            artists: vec!["Artist".to_owned()],
+            country: "US".to_owned(),
+            created_at: Utc::now(),
+            artwork_url: None,
+            duration_ms: Some(180_000),
+            equivalent_links: vec![],
+            last_interacted_at: Utc::now(),
+            last_resolved_at: Utc::now(),
+        }
+    }
+
+    const VISIBLE_CONFIDENCE: u8 = 75;
+
+    fn link_for(platform: MusicPlatform) -> String {
+        format!("https://example.com/{}", platform.as_ref())
+    }
+
+    fn exact_link_for(platform: MusicPlatform) -> PlatformLink {
+        PlatformLink::exact(link_for(platform))
+    }
+
+    fn assert_has_every_platform(response: &MusicLinkResponse) {
+        let platforms: Vec<_> = response
+            .collected_links
+            .iter()
+            .map(|link| link.platform)
+            .collect();
+        assert_eq!(platforms, MusicPlatform::iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn response_covers_every_combination_of_present_platforms() {
+        let platforms = [
+            MusicPlatform::Spotify,
+            MusicPlatform::AppleMusic,
+            MusicPlatform::YoutubeMusic,
+        ];
+        for mask in 0..(1 << platforms.len()) {
+            let present: Vec<_> = platforms
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| mask & (1 << idx) != 0)
+                .map(|(_, platform)| *platform)
+                .collect();
+            let platform_links = present
+                .iter()
+                .map(|platform| (*platform, exact_link_for(*platform)))
+                .collect();
+
+            let response =
+                build_music_link_response(stored_music_link(), platform_links, VISIBLE_CONFIDENCE);
+
+            assert_has_every_platform(&response);
+            assert_eq!(usize::from(response.found), present.len(), "mask {mask}");
+            for link in &response.collected_links {
+                let expected = present
+                    .contains(&link.platform)
+                    .then(|| link_for(link.platform));
+                assert_eq!(link.link, expected, "mask {mask}");
+            }
+        }
+    }
+
+    #[test]
+    fn response_counts_every_platform_when_all_are_present() {
+        let platform_links = MusicPlatform::iter()
+            .map(|platform| (platform, exact_link_for(platform)))
+            .collect();
+
+        let response =
+            build_music_link_response(stored_music_link(), platform_links, VISIBLE_CONFIDENCE);
+
+        assert_has_every_platform(&response);
+        assert_eq!(usize::from(response.found), MusicPlatform::iter().count());
+    }
+
+    #[test]
+    fn response_without_platforms_lists_every_platform_as_missing() {
+        let response =
+            build_music_link_response(stored_music_link(), HashMap::new(), VISIBLE_CONFIDENCE);
+
+        assert_has_every_platform(&response);
+        assert_eq!(response.found, 0);
+        assert!(
+            response
+                .collected_links
+                .iter()
+                .all(|link| link.link.is_none())
+        );
+    }
+
+    #[test]
+    fn response_keeps_stored_id_and_metadata() {
+        let music_link = stored_music_link();
+        let id = music_link.id;
+
+        let response = build_music_link_response(music_link, HashMap::new(), VISIBLE_CONFIDENCE);
+
+        assert_eq!(response.id, id);
+        assert_eq!(response.metadata.title.as_deref(), Some("Title"));
+        assert_eq!(response.metadata.artists, vec!["Artist".to_owned()]);
+        assert_eq!(response.metadata.duration_ms, Some(180_000));
+    }
+
+    #[test]
+    fn response_hides_low_confidence_links_and_flags_best_guesses() {
+        let guess = |confidence| PlatformLink {
+            link: link_for(MusicPlatform::Deezer),
+            confidence,
+        };
+        let platform_links = HashMap::from([
+            (
+                MusicPlatform::Spotify,
+                exact_link_for(MusicPlatform::Spotify),
+            ),
+            (MusicPlatform::Deezer, guess(VISIBLE_CONFIDENCE)),
+            (MusicPlatform::Tidal, guess(VISIBLE_CONFIDENCE - 1)),
+        ]);
+
+        let response =
+            build_music_link_response(stored_music_link(), platform_links, VISIBLE_CONFIDENCE);
+
+        let link = |platform| {
+            response
+                .collected_links
+                .iter()
+                .find(|link| link.platform == platform)
+                .unwrap()
+        };
+        assert_eq!(response.found, 2);
+        assert!(!link(MusicPlatform::Spotify).is_best_guess());
+        assert!(link(MusicPlatform::Deezer).is_best_guess());
+        assert_eq!(link(MusicPlatform::Tidal).link, None);
+        assert!(!link(MusicPlatform::Tidal).is_best_guess());
+    }
 }
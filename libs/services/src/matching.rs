@@ -0,0 +1,465 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    canonical::canonicalize_url,
+    config::MatchingConfig,
+    error::Result,
+    models::{EXACT_MATCH_CONFIDENCE, MusicEntityKind, MusicMetadata, MusicPlatform, PlatformLink},
+    resolvers::{MusicSearchResolver, SearchCandidate},
+};
+
+/// Separators people put between the artist and the title of a song.
+static TEXT_SEPARATORS: [&str; 4] = [" - ", " – ", " — ", " by "];
+
+/// Lowercases and strips punctuation, bracketed suffixes like "(Remastered)"
+/// and featured artists so that cosmetic differences do not lower the score.
+fn normalize(value: &str) -> String {
+    let mut depth = 0u32;
+    let without_brackets: String = value
+        .chars()
+        .filter(|c| match c {
+            '(' | '[' => {
+                depth += 1;
+                false
+            }
+            ')' | ']' => {
+                depth = depth.saturating_sub(1);
+                false
+            }
+            _ => depth == 0,
+        })
+        .collect();
+    let lowercase = without_brackets.to_lowercase();
+    let without_features = [" feat.", " feat ", " ft.", " featuring "]
+        .iter()
+        .filter_map(|marker| lowercase.find(marker))
+        .min()
+        .map_or(lowercase.as_str(), |idx| &lowercase[..idx]);
+    without_features
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Similarity of two strings after normalization, from 0.0 to 1.0.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = normalize(a).chars().collect();
+    let b: Vec<char> = normalize(b).chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// Best similarity between any artist of the query and any of the candidate.
+fn artist_similarity(query: &[String], candidate: &[String]) -> f64 {
+    query
+        .iter()
+        .flat_map(|a| candidate.iter().map(move |b| similarity(a, b)))
+        .fold(0.0, f64::max)
+}
+
+fn duration_similarity(query: i32, candidate: i32, config: &MatchingConfig) -> f64 {
+    let tolerance = config.duration_tolerance.as_millis() as f64;
+    let difference = f64::from((query - candidate).abs());
+    if difference <= tolerance {
+        return 1.0;
+    }
+    (1.0 - (difference - tolerance) / (2.0 * tolerance)).max(0.0)
+}
+
+/// UPCs are sometimes reported as 13 digit EANs with a leading zero.
+fn same_upc(a: &str, b: &str) -> bool {
+    a.trim_start_matches('0') == b.trim_start_matches('0')
+}
+
+/// Scores how likely `candidate` is the same entity as `query`, from 0 to 100.
+///
+/// Only an identical ISRC (for songs) or UPC (for albums) scores 100, and a
+/// different one scores 0, since it belongs to another recording or release
+/// such as a live version or a remaster. When either side has no identifier
+/// the score combines title, artist and, when both are known, duration
+/// similarity.
+pub fn score_candidate(
+    kind: MusicEntityKind,
+    query: &MusicMetadata,
+    candidate: &MusicMetadata,
+    config: &MatchingConfig,
+) -> u8 {
+    let identifiers_match = match kind {
+        MusicEntityKind::Song => query
+            .isrc
+            .as_ref()
+            .zip(candidate.isrc.as_ref())
+            .map(|(a, b)| a.eq_ignore_ascii_case(b)),
+        MusicEntityKind::Album => query
+            .upc
+            .as_ref()
+            .zip(candidate.upc.as_ref())
+            .map(|(a, b)| same_upc(a, b)),
+        MusicEntityKind::Artist | MusicEntityKind::Playlist => None,
+    };
+    match identifiers_match {
+        Some(true) => return EXACT_MATCH_CONFIDENCE,
+        Some(false) => return 0,
+        None => {}
+    }
+    let (Some(query_title), Some(candidate_title)) = (&query.title, &candidate.title) else {
+        return 0;
+    };
+    let title = similarity(query_title, candidate_title);
+    let artists = artist_similarity(&query.artists, &candidate.artists);
+    let score = match (query.duration_ms, candidate.duration_ms) {
+        (Some(a), Some(b)) => 0.5 * title + 0.3 * artists + 0.2 * duration_similarity(a, b, config),
+        _ => 0.6 * title + 0.4 * artists,
+    };
+    // Fuzzy matches stay below the score reserved for exact identifier matches.
+    (score * f64::from(EXACT_MATCH_CONFIDENCE - 1)).round() as u8
+}
+
+/// Scores how likely `candidate` is the song described by free `text`, from 0 to 99.
+///
+/// Text with a separator is tried as both "artist - title" and "title - artist".
+/// Otherwise it is compared with the candidate's artists and title together.
+pub fn score_text_candidate(text: &str, candidate: &MusicMetadata, config: &MatchingConfig) -> u8 {
+    if let Some((left, right)) = TEXT_SEPARATORS
+        .iter()
+        .find_map(|separator| text.split_once(separator))
+    {
+        let query = |artist: &str, title: &str| MusicMetadata {
+            title: Some(title.trim().to_owned()),
+            artists: vec![artist.trim().to_owned()],
+            ..Default::default()
+        };
+        let forward = score_candidate(
+            MusicEntityKind::Song,
+            &query(left, right),
+            candidate,
+            config,
+        );
+        let reverse = score_candidate(
+            MusicEntityKind::Song,
+            &query(right, left),
+            candidate,
+            config,
+        );
+        return forward.max(reverse);
+    }
+    let Some(title) = &candidate.title else {
+        return 0;
+    };
+    let artists = candidate.artists.join(" ");
+    let score = [
+        similarity(text, &format!("{artists} {title}")),
+        similarity(text, &format!("{title} {artists}")),
+        // A title on its own can not confirm the artist, like `score_candidate`.
+        0.6 * similarity(text, title),
+    ]
+    .into_iter()
+    .fold(0.0, f64::max);
+    (score * f64::from(EXACT_MATCH_CONFIDENCE - 1)).round() as u8
+}
+
+/// Finds a song or album on platforms that no resolver returned a link for, by
+/// searching each platform and keeping the best scoring candidate.
+pub struct MusicLinkMatcher {
+    config: MatchingConfig,
+    searchers: Vec<Arc<dyn MusicSearchResolver>>,
+}
+
+impl MusicLinkMatcher {
+    pub fn new(config: MatchingConfig, searchers: Vec<Arc<dyn MusicSearchResolver>>) -> Self {
+        Self { config, searchers }
+    }
+
+    /// Searches every platform not already in `existing` and returns the
+    /// matches that scored at least `min_confidence`.
+    ///
+    /// Matching is best effort, so search failures are logged and skipped.
+    pub async fn find_matches(
+        &self,
+        kind: MusicEntityKind,
+        metadata: &MusicMetadata,
+        existing: &HashMap<MusicPlatform, PlatformLink>,
+    ) -> HashMap<MusicPlatform, PlatformLink> {
+        let mut matches = HashMap::new();
+        if !matches!(kind, MusicEntityKind::Song | MusicEntityKind::Album) {
+            return matches;
+        }
+        if metadata.title.is_none() && metadata.isrc.is_none() && metadata.upc.is_none() {
+            tracing::debug!("Nothing to match on for {:?}", kind);
+            return matches;
+        }
+        for searcher in &self.searchers {
+            let platform = searcher.platform();
+            if existing.contains_key(&platform) {
+                continue;
+            }
+            let candidates = match searcher.search(kind, metadata).await {
+                Ok(candidates) => candidates,
+                Err(e) => {
+                    tracing::warn!("Searching {:?} failed: {}", platform, e);
+                    continue;
+                }
+            };
+            let best = candidates
+                .into_iter()
+                .map(|candidate| {
+                    let score = score_candidate(kind, metadata, &candidate.metadata, &self.config);
+                    (score, candidate)
+                })
+                .max_by_key(|(score, _)| *score);
+            match best {
+                Some((confidence, candidate)) if confidence >= self.config.min_confidence => {
+                    tracing::debug!(
+                        "Matched {:?} link {} with confidence {}",
+                        platform,
+                        candidate.link,
+                        confidence
+                    );
+                    let link = canonicalize_url(&candidate.link);
+                    matches.insert(platform, PlatformLink { link, confidence });
+                }
+                Some((confidence, _)) => {
+                    tracing::debug!("Best {:?} candidate scored only {}", platform, confidence);
+                }
+                None => tracing::debug!("No {:?} candidates found", platform),
+            }
+        }
+        matches
+    }
+
+    /// Searches every platform for free `text` and returns the best scoring
+    /// song, if it scored at least `min_confidence`.
+    ///
+    /// Fails only when every search failed, with the last error.
+    pub async fn search_text(&self, text: &str) -> Result<Option<(SearchCandidate, u8)>> {
+        let mut best: Option<(SearchCandidate, u8)> = None;
+        let mut last_error = None;
+        let mut any_succeeded = false;
+        for searcher in &self.searchers {
+            let candidates = match searcher.search_text(text).await {
+                Ok(candidates) => candidates,
+                Err(e) => {
+                    tracing::warn!("Searching {:?} for text failed: {}", searcher.platform(), e);
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            any_succeeded = true;
+            for candidate in candidates {
+                let score = score_text_candidate(text, &candidate.metadata, &self.config);
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, best_score)| score > *best_score)
+                {
+                    best = Some((candidate, score));
+                }
+            }
+        }
+        if !any_succeeded && let Some(e) = last_error {
+            return Err(e);
+        }
+        Ok(best.filter(|(_, score)| *score >= self.config.min_confidence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn song(title: &str, artist: &str, duration_ms: Option<i32>) -> MusicMetadata {
+        MusicMetadata {
+            title: Some(title.to_owned()),
+            artists: vec![artist.to_owned()],
+            duration_ms,
+            ..Default::default()
+        }
+    }
+
+    fn with_isrc(metadata: MusicMetadata, isrc: &str) -> MusicMetadata {
+        MusicMetadata {
+            isrc: Some(isrc.to_owned()),
+            ..metadata
+        }
+    }
+
+    fn album(title: &str, upc: &str) -> MusicMetadata {
+        MusicMetadata {
+            title: Some(title.to_owned()),
+            artists: vec!["Rick Astley".to_owned()],
+            upc: Some(upc.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn score(query: &MusicMetadata, candidate: &MusicMetadata) -> u8 {
+        score_candidate(
+            MusicEntityKind::Song,
+            query,
+            candidate,
+            &MatchingConfig::default(),
+        )
+    }
+
+    fn original() -> MusicMetadata {
+        song("Never Gonna Give You Up", "Rick Astley", Some(213_000))
+    }
+
+    #[test]
+    fn same_isrc_is_an_exact_match() {
+        let query = with_isrc(original(), "GBARL9300135");
+        let candidate = with_isrc(song("Something else", "Someone", None), "gbarl9300135");
+
+        assert_eq!(score(&query, &candidate), EXACT_MATCH_CONFIDENCE);
+    }
+
+    #[test]
+    fn different_isrc_is_rejected_even_with_the_same_title() {
+        let query = with_isrc(original(), "GBARL9300135");
+        let live = with_isrc(
+            song(
+                "Never Gonna Give You Up (Live)",
+                "Rick Astley",
+                Some(213_000),
+            ),
+            "GBUM71900001",
+        );
+
+        assert_eq!(score(&query, &live), 0);
+    }
+
+    #[test]
+    fn identical_metadata_without_isrc_is_visible_but_not_exact() {
+        let config = MatchingConfig::default();
+        let confidence = score(&original(), &original());
+
+        assert!(confidence >= config.visible_confidence, "{confidence}");
+        assert!(confidence < EXACT_MATCH_CONFIDENCE);
+    }
+
+    #[test]
+    fn isrc_on_one_side_only_falls_back_to_metadata() {
+        let query = with_isrc(original(), "GBARL9300135");
+        let config = MatchingConfig::default();
+
+        assert!(score(&query, &original()) >= config.visible_confidence);
+    }
+
+    #[test]
+    fn cosmetic_title_differences_stay_visible() {
+        let candidate = song(
+            "Never Gonna Give You Up (2022 Remaster) [feat. Nobody]",
+            "RICK ASTLEY",
+            Some(214_500),
+        );
+        let config = MatchingConfig::default();
+
+        assert!(score(&original(), &candidate) >= config.visible_confidence);
+    }
+
+    #[test]
+    fn same_title_by_another_artist_is_not_visible() {
+        let query = song("Hello", "Adele", Some(295_000));
+        let candidate = song("Hello", "Lionel Richie", Some(249_000));
+        let config = MatchingConfig::default();
+
+        assert!(score(&query, &candidate) < config.visible_confidence);
+    }
+
+    #[test]
+    fn unrelated_song_is_below_min_confidence() {
+        let candidate = song("Bohemian Rhapsody", "Queen", Some(354_000));
+        let config = MatchingConfig::default();
+
+        assert!(score(&original(), &candidate) < config.min_confidence);
+    }
+
+    #[test]
+    fn duration_counts_fully_within_tolerance_and_not_past_three_times_it() {
+        let config = MatchingConfig {
+            duration_tolerance: Duration::from_secs(3),
+            ..Default::default()
+        };
+
+        assert_eq!(duration_similarity(200_000, 202_000, &config), 1.0);
+        assert_eq!(duration_similarity(200_000, 206_000, &config), 0.5);
+        assert_eq!(duration_similarity(200_000, 210_000, &config), 0.0);
+    }
+
+    #[test]
+    fn missing_title_scores_zero() {
+        let candidate = MusicMetadata {
+            artists: vec!["Rick Astley".to_owned()],
+            ..Default::default()
+        };
+
+        assert_eq!(score(&original(), &candidate), 0);
+    }
+
+    #[test]
+    fn albums_match_on_upc_ignoring_leading_zeros() {
+        let config = MatchingConfig::default();
+        let score = |query: &MusicMetadata, candidate: &MusicMetadata| {
+            score_candidate(MusicEntityKind::Album, query, candidate, &config)
+        };
+        let query = album("Whenever You Need Somebody", "4050538826586");
+
+        assert_eq!(
+            score(&query, &album("Other", "04050538826586")),
+            EXACT_MATCH_CONFIDENCE
+        );
+        assert_eq!(
+            score(
+                &query,
+                &album("Whenever You Need Somebody", "5099749432727")
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn text_matches_artist_and_title_in_either_order() {
+        let config = MatchingConfig::default();
+        for text in [
+            "Rick Astley - Never Gonna Give You Up",
+            "Never Gonna Give You Up - Rick Astley",
+            "never gonna give you up by rick astley",
+            "rick astley never gonna give you up",
+        ] {
+            let confidence = score_text_candidate(text, &original(), &config);
+            assert!(
+                confidence >= config.visible_confidence,
+                "{text}: {confidence}"
+            );
+        }
+    }
+
+    #[test]
+    fn text_with_only_the_title_is_not_visible() {
+        let config = MatchingConfig::default();
+        let confidence = score_text_candidate("Never Gonna Give You Up", &original(), &config);
+
+        assert!(confidence < config.visible_confidence, "{confidence}");
+    }
+}
@@ -2,15 +2,10 @@ use std::collections::HashMap;
 
 use nest_struct::nest_struct;
 use serde::{Deserialize, Serialize};
-use strum::EnumIter;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, EnumIter)]
-pub enum MusicPlatform {
-    Spotify,
-    AppleMusic,
-    YoutubeMusic,
-}
+pub use entities::music_link::MusicEntityKind;
+pub use entities::music_link_platform::MusicPlatform;
 
 #[derive(Debug)]
 pub struct MusicLinkInput {
@@ -18,16 +13,78 @@ pub struct MusicLinkInput {
     pub user_country: String,
 }
 
+/// Free text such as "artist - title" to look a song up by.
 #[derive(Debug)]
+pub struct MusicSearchInput {
+    pub query: String,
+    pub user_country: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MusicLinkData {
     pub link: Option<String>,
     pub platform: MusicPlatform,
+    /// How sure the match is, from 0 to 100. Links below 100 are best guesses.
+    pub confidence: u8,
+}
+
+impl MusicLinkData {
+    pub fn is_best_guess(&self) -> bool {
+        self.link.is_some() && self.confidence < EXACT_MATCH_CONFIDENCE
+    }
+}
+
+/// Confidence of links that a resolver or an exact ISRC/UPC match vouched for.
+pub const EXACT_MATCH_CONFIDENCE: u8 = 100;
+
+/// A link on a single platform together with how sure the match is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformLink {
+    pub link: String,
+    pub confidence: u8,
+}
+
+impl PlatformLink {
+    pub fn exact(link: String) -> Self {
+        Self {
+            link,
+            confidence: EXACT_MATCH_CONFIDENCE,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MusicMetadata {
+    pub upc: Option<String>,
+    pub isrc: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub artwork_url: Option<String>,
+    pub duration_ms: Option<i32>,
+}
+
+impl MusicMetadata {
+    /// Fills every field that is still empty with the value from `other`.
+    pub fn fill_missing_from(&mut self, other: MusicMetadata) {
+        self.upc = self.upc.take().or(other.upc);
+        self.isrc = self.isrc.take().or(other.isrc);
+        self.album = self.album.take().or(other.album);
+        self.title = self.title.take().or(other.title);
+        if self.artists.is_empty() {
+            self.artists = other.artists;
+        }
+        self.artwork_url = self.artwork_url.take().or(other.artwork_url);
+        self.duration_ms = self.duration_ms.or(other.duration_ms);
+    }
 }
 
 #[derive(Debug)]
 pub struct MusicLinkResponse {
     pub id: Uuid,
     pub found: u8,
+    pub kind: MusicEntityKind,
+    pub metadata: MusicMetadata,
     pub collected_links: Vec<MusicLinkData>,
 }
 
@@ -40,6 +97,24 @@ pub mod providers {
         Spotify,
         AppleMusic,
         YoutubeMusic,
+        Youtube,
+        Deezer,
+        Tidal,
+        Soundcloud,
+        AmazonMusic,
+        AmazonStore,
+        Bandcamp,
+        Pandora,
+        Napster,
+        Audiomack,
+        Anghami,
+        Boomplay,
+        Audius,
+        Yandex,
+        Spinrilla,
+        Itunes,
+        Google,
+        GoogleStore,
         #[serde(untagged)]
         Unknown(String),
     }
@@ -54,6 +129,11 @@ pub mod providers {
             String,
             nest! {
                 pub id: String,
+                #[serde(rename = "type")]
+                pub kind: Option<String>,
+                pub title: Option<String>,
+                pub artist_name: Option<String>,
+                pub thumbnail_url: Option<String>,
                 pub platforms: Vec<SongLinkPlatform>,
             },
         >,
@@ -64,4 +144,122 @@ pub mod providers {
             },
         >,
     }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifyTokenResponse {
+        pub access_token: String,
+        pub expires_in: u64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifyArtist {
+        pub name: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifyImage {
+        pub url: String,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    pub struct SpotifyExternalIds {
+        pub isrc: Option<String>,
+        pub upc: Option<String>,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    pub struct SpotifyExternalUrls {
+        pub spotify: Option<String>,
+    }
+
+    #[nest_struct]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifyTrack {
+        pub id: String,
+        pub name: String,
+        pub duration_ms: i32,
+        pub artists: Vec<SpotifyArtist>,
+        pub album: nest! {
+            pub name: String,
+            #[serde(default)]
+            pub images: Vec<SpotifyImage>,
+        },
+        #[serde(default)]
+        pub external_ids: SpotifyExternalIds,
+        #[serde(default)]
+        pub external_urls: SpotifyExternalUrls,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifyAlbum {
+        pub id: String,
+        pub name: String,
+        pub artists: Vec<SpotifyArtist>,
+        #[serde(default)]
+        pub images: Vec<SpotifyImage>,
+        #[serde(default)]
+        pub external_ids: SpotifyExternalIds,
+        #[serde(default)]
+        pub external_urls: SpotifyExternalUrls,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifyPage<T> {
+        pub items: Vec<T>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SpotifySearchResponse {
+        pub tracks: Option<SpotifyPage<SpotifyTrack>>,
+        pub albums: Option<SpotifyPage<SpotifyAlbum>>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DeezerArtist {
+        pub name: String,
+    }
+
+    #[nest_struct]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DeezerTrack {
+        pub id: u64,
+        pub link: String,
+        pub title: String,
+        pub isrc: Option<String>,
+        pub duration: Option<i32>,
+        pub artist: DeezerArtist,
+        pub album: nest! {
+            pub title: String,
+            pub cover_xl: Option<String>,
+        },
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DeezerAlbum {
+        pub id: u64,
+        pub link: String,
+        pub title: String,
+        pub upc: Option<String>,
+        pub cover_xl: Option<String>,
+        pub artist: DeezerArtist,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DeezerSearchResponse<T> {
+        pub data: Vec<T>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DeezerError {
+        pub message: String,
+        pub code: Option<i32>,
+    }
+
+    /// Deezer reports errors with a `200 OK` and an `error` object in the body.
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum DeezerResult<T> {
+        Error { error: DeezerError },
+        Ok(T),
+    }
 }
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use entities::{
+    music_link, music_link_platform,
+    prelude::{MusicLink, MusicLinkPlatform},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, DatabaseConnection, EntityTrait,
+    ModelTrait, QueryFilter, QueryOrder, QuerySelect, Select,
+    prelude::Expr,
+    sea_query::{OnConflict, Query},
+};
+
+use crate::{
+    MusicLinkService,
+    canonical::canonicalize_url,
+    config::MusicLinkRefreshConfig,
+    error::{MusicLinkError, Result},
+    models::{MusicLinkInput, MusicMetadata, MusicPlatform, PlatformLink},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MusicLinkRefreshSummary {
+    pub checked: u64,
+    pub failed: u64,
+    pub links_added: u64,
+}
+
+/// Selects the music links due for a refresh at `now`: recently used links that
+/// are stale, or that lost a platform because one of their links went dead.
+///
+/// Comparing against every supported platform would match nearly every link,
+/// as no track is on all of them, so only platforms a link had count as missing.
+fn refresh_candidates(config: &MusicLinkRefreshConfig, now: DateTime<Utc>) -> Select<MusicLink> {
+    let stale_before = now - chrono::Duration::from_std(config.stale_after).unwrap_or_default();
+    let missing_before =
+        now - chrono::Duration::from_std(config.missing_platforms_after).unwrap_or_default();
+    let active_since = now - chrono::Duration::from_std(config.active_within).unwrap_or_default();
+    let lost_platform = Query::select()
+        .expr(Expr::val(1))
+        .from(MusicLinkPlatform)
+        .and_where(
+            Expr::col((MusicLinkPlatform, music_link_platform::Column::MusicLinkId))
+                .equals((MusicLink, music_link::Column::Id)),
+        )
+        .and_where(
+            Expr::col((MusicLinkPlatform, music_link_platform::Column::IsAvailable)).eq(false),
+        )
+        .to_owned();
+    MusicLink::find()
+        .filter(music_link::Column::LastInteractedAt.gte(active_since))
+        .filter(
+            Condition::any()
+                .add(music_link::Column::LastResolvedAt.lt(stale_before))
+                .add(
+                    Condition::all()
+                        .add(music_link::Column::LastResolvedAt.lt(missing_before))
+                        .add(Expr::exists(lost_platform)),
+                ),
+        )
+        .order_by_desc(music_link::Column::LastInteractedAt)
+        .order_by_asc(music_link::Column::LastResolvedAt)
+        .limit(config.budget)
+}
+
+/// Drops found links that are the same as a link already marked dead for
+/// their platform, since storing them again would mark them available.
+fn drop_known_dead_links(
+    found: &mut HashMap<MusicPlatform, PlatformLink>,
+    dead_links: &HashMap<MusicPlatform, String>,
+) {
+    found.retain(|platform, found| dead_links.get(platform) != Some(&found.link));
+}
+
+impl MusicLinkService {
+    /// Re-resolves a music link and stores links for the platforms it is
+    /// missing, replacing links that went dead. A platform that resolves to the
+    /// same dead link again keeps it marked as dead.
+    pub(crate) async fn refresh_music_link(
+        &self,
+        music_link: music_link::Model,
+        db: &DatabaseConnection,
+    ) -> Result<u64> {
+        let existing = self.get_platform_links_from_db(&music_link, db).await?;
+        let dead_links: HashMap<MusicPlatform, String> = music_link
+            .find_related(MusicLinkPlatform)
+            .filter(music_link_platform::Column::IsAvailable.eq(false))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|platform_link| (platform_link.platform, platform_link.link))
+            .collect();
+        let source = existing
+            .get(&MusicPlatform::Spotify)
+            .or_else(|| existing.values().next())
+            .map(|platform_link| &platform_link.link)
+            .or_else(|| music_link.equivalent_links.first())
+            .cloned();
+        let mut metadata = MusicMetadata {
+            upc: music_link.upc.clone(),
+            isrc: music_link.isrc.clone(),
+            album: music_link.album.clone(),
+            title: music_link.title.clone(),
+            artists: music_link.artists.clone(),
+            artwork_url: music_link.artwork_url.clone(),
+            duration_ms: music_link.duration_ms,
+        };
+        let mut new_links = HashMap::new();
+        if let Some(source) = source {
+            let input = MusicLinkInput {
+                link: source,
+                user_country: music_link.country.clone(),
+            };
+            match self.resolve_with_chain(&input).await {
+                Ok(resolved) => {
+                    new_links.extend(
+                        resolved
+                            .links
+                            .into_iter()
+                            .filter(|(platform, _)| !existing.contains_key(platform))
+                            .map(|(platform, link)| {
+                                (platform, PlatformLink::exact(canonicalize_url(&link)))
+                            }),
+                    );
+                    metadata.fill_missing_from(resolved.metadata);
+                }
+                Err(e) if e.is_transient() => return Err(e),
+                Err(e) => tracing::debug!("No links found for {}: {}", input.link, e),
+            }
+        }
+        let mut known_links = existing;
+        known_links.extend(new_links.clone());
+        new_links.extend(
+            self.matcher
+                .find_matches(music_link.kind, &metadata, &known_links)
+                .await,
+        );
+        drop_known_dead_links(&mut new_links, &dead_links);
+        let mut links_added = 0;
+        if !new_links.is_empty() {
+            let platforms: Vec<_> = new_links.keys().copied().collect();
+            MusicLinkPlatform::delete_many()
+                .filter(music_link_platform::Column::MusicLinkId.eq(music_link.id))
+                .filter(music_link_platform::Column::Platform.is_in(platforms))
+                .filter(music_link_platform::Column::IsAvailable.eq(false))
+                .exec(db)
+                .await?;
+            let to_insert: Vec<_> = new_links
+                .into_iter()
+                .map(|(platform, link)| music_link_platform::ActiveModel {
+                    link: ActiveValue::Set(link.link),
+                    music_link_id: ActiveValue::Set(music_link.id),
+                    platform: ActiveValue::Set(platform),
+                    confidence: ActiveValue::Set(i16::from(link.confidence)),
+                    ..Default::default()
+                })
+                .collect();
+            links_added = MusicLinkPlatform::insert_many(to_insert)
+                .on_conflict(OnConflict::new().do_nothing().to_owned())
+                .exec_without_returning(db)
+                .await?;
+        }
+        let mut active: music_link::ActiveModel = music_link.into();
+        active.upc = ActiveValue::Set(metadata.upc);
+        active.isrc = ActiveValue::Set(metadata.isrc);
+        active.album = ActiveValue::Set(metadata.album);
+        active.title = ActiveValue::Set(metadata.title);
+        active.artists = ActiveValue::Set(metadata.artists);
+        active.artwork_url = ActiveValue::Set(metadata.artwork_url);
+        active.duration_ms = ActiveValue::Set(metadata.duration_ms);
+        active.last_resolved_at = ActiveValue::Set(Utc::now());
+        active.update(db).await?;
+        Ok(links_added)
+    }
+
+    /// Re-queries the resolver chain for music links that are stale or lost a
+    /// platform, adding any newly found links and metadata, then searches for
+    /// the platforms that are still missing.
+    ///
+    /// Only links interacted with recently are picked, the most recently used
+    /// first.
+    ///
+    /// The run stops early when upstream is unavailable so the remaining budget
+    /// is not spent on requests that are bound to fail.
+    pub async fn refresh_stale_music_links(
+        &self,
+        config: &MusicLinkRefreshConfig,
+        db: &DatabaseConnection,
+    ) -> Result<MusicLinkRefreshSummary> {
+        let candidates = refresh_candidates(config, Utc::now()).all(db).await?;
+        tracing::info!("Found {} music links to refresh", candidates.len());
+
+        let mut summary = MusicLinkRefreshSummary::default();
+        for music_link in candidates {
+            let id = music_link.id;
+            summary.checked += 1;
+            let result = self.refresh_music_link(music_link, db).await;
+            self.cache.invalidate(id);
+            match result {
+                Ok(links_added) => summary.links_added += links_added,
+                Err(MusicLinkError::Database(e)) => return Err(e.into()),
+                Err(e) => {
+                    tracing::warn!("Stopping refresh at music link {}: {}", id, e);
+                    summary.failed += 1;
+                    break;
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    use super::*;
+
+    const DEAD_LINK: &str = "https://open.spotify.com/track/dead";
+
+    fn dead_spotify_link() -> HashMap<MusicPlatform, String> {
+        HashMap::from([(MusicPlatform::Spotify, DEAD_LINK.to_owned())])
+    }
+
+    #[test]
+    fn dead_link_found_again_stays_dead() {
+        let mut found = HashMap::from([
+            (
+                MusicPlatform::Spotify,
+                PlatformLink::exact(DEAD_LINK.to_owned()),
+            ),
+            (
+                MusicPlatform::Deezer,
+                PlatformLink::exact("https://www.deezer.com/track/1".to_owned()),
+            ),
+        ]);
+
+        drop_known_dead_links(&mut found, &dead_spotify_link());
+
+        assert!(!found.contains_key(&MusicPlatform::Spotify));
+        assert!(found.contains_key(&MusicPlatform::Deezer));
+    }
+
+    #[test]
+    fn dead_link_is_replaced_by_a_different_one() {
+        let replacement = "https://open.spotify.com/track/alive".to_owned();
+        let mut found = HashMap::from([(
+            MusicPlatform::Spotify,
+            PlatformLink::exact(replacement.clone()),
+        )]);
+
+        drop_known_dead_links(&mut found, &dead_spotify_link());
+
+        assert_eq!(found[&MusicPlatform::Spotify].link, replacement);
+    }
+
+    #[test]
+    fn refresh_candidates_are_stale_or_lost_a_platform() {
+        let config = MusicLinkRefreshConfig::default();
+        let now = Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+        let sql = refresh_candidates(&config, now)
+            .build(DbBackend::Postgres)
+            .to_string();
+
+        let (_, filter) = sql.split_once(" WHERE ").unwrap();
+        assert_eq!(
+            filter,
+            "\"music_link\".\"last_interacted_at\" >= '2025-03-03 12:00:00.000000 +00:00' \
+             AND (\"music_link\".\"last_resolved_at\" < '2025-05-02 12:00:00.000000 +00:00' \
+             OR (\"music_link\".\"last_resolved_at\" < '2025-05-25 12:00:00.000000 +00:00' \
+             AND EXISTS(SELECT 1 FROM \"music_link_platform\" \
+             WHERE \"music_link_platform\".\"music_link_id\" = \"music_link\".\"id\" \
+             AND \"music_link_platform\".\"is_available\" = FALSE))) \
+             ORDER BY \"music_link\".\"last_interacted_at\" DESC, \
+             \"music_link\".\"last_resolved_at\" ASC LIMIT 10"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+
+use super::{MusicSearchResolver, SearchCandidate, text_query};
+use crate::{
+    config::DeezerConfig,
+    error::{MusicLinkError, Result},
+    models::{
+        MusicEntityKind, MusicMetadata, MusicPlatform,
+        providers::{DeezerAlbum, DeezerResult, DeezerSearchResponse, DeezerTrack},
+    },
+    upstream::UpstreamGuard,
+    utils::error_for_status,
+};
+
+/// Deezer error code for lookups that matched nothing.
+const NO_DATA_ERROR_CODE: i32 = 800;
+/// Deezer error code for exceeding the request quota.
+const QUOTA_ERROR_CODE: i32 = 4;
+
+/// Searches the public Deezer API, which needs no credentials.
+pub struct DeezerResolver {
+    client: Client,
+    config: DeezerConfig,
+    guard: Arc<UpstreamGuard>,
+}
+
+impl DeezerResolver {
+    pub fn new(client: Client, config: DeezerConfig) -> Self {
+        let guard = UpstreamGuard::new("deezer", config.upstream.clone());
+        Self::with_guard(client, config, Arc::new(guard))
+    }
+
+    pub fn guard(&self) -> Arc<UpstreamGuard> {
+        self.guard.clone()
+    }
+
+    /// Creates a resolver whose requests go through a shared `guard`.
+    pub fn with_guard(client: Client, config: DeezerConfig, guard: Arc<UpstreamGuard>) -> Self {
+        Self {
+            client,
+            config,
+            guard,
+        }
+    }
+
+    fn api_url(&self, path: &str, params: &[(&str, &str)]) -> Result<Url> {
+        let url = format!("{}/{path}", self.config.api_base_url.trim_end_matches('/'));
+        Url::parse_with_params(&url, params)
+            .map_err(|e| MusicLinkError::UpstreamUnavailable(e.to_string()))
+    }
+
+    /// Fetches `url`, returning `None` when Deezer has no data for it.
+    async fn fetch<T: DeserializeOwned>(&self, url: Url) -> Result<Option<T>> {
+        let response = self.client.get(url.clone()).send().await?;
+        let response = error_for_status("deezer", response, url.as_str())?;
+        let result = response.json::<DeezerResult<T>>().await.map_err(|e| {
+            MusicLinkError::UpstreamUnavailable(format!("Invalid Deezer response: {e}"))
+        })?;
+        match result {
+            DeezerResult::Ok(value) => Ok(Some(value)),
+            DeezerResult::Error { error } => match error.code {
+                Some(NO_DATA_ERROR_CODE) => Ok(None),
+                Some(QUOTA_ERROR_CODE) => Err(MusicLinkError::RateLimited { retry_after: None }),
+                _ => Err(MusicLinkError::UpstreamUnavailable(format!(
+                    "Deezer error: {}",
+                    error.message
+                ))),
+            },
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<Option<T>> {
+        self.guard.call(|| self.fetch(url.clone())).await
+    }
+
+    pub async fn get_track_by_isrc(&self, isrc: &str) -> Result<Option<DeezerTrack>> {
+        let url = self.api_url(&format!("track/isrc:{isrc}"), &[])?;
+        self.get(url).await
+    }
+
+    pub async fn get_album_by_upc(&self, upc: &str) -> Result<Option<DeezerAlbum>> {
+        let url = self.api_url(&format!("album/upc:{upc}"), &[])?;
+        self.get(url).await
+    }
+
+    /// Runs an advanced search query against one of the search endpoints.
+    async fn run_search<T: DeserializeOwned>(&self, path: &str, query: &str) -> Result<Vec<T>> {
+        let url = self.api_url(path, &[("q", query)])?;
+        let response: Option<DeezerSearchResponse<T>> = self.get(url).await?;
+        Ok(response.map(|response| response.data).unwrap_or_default())
+    }
+}
+
+fn track_candidate(track: DeezerTrack) -> SearchCandidate {
+    let metadata = MusicMetadata {
+        isrc: track.isrc,
+        album: Some(track.album.title),
+        title: Some(track.title),
+        artists: vec![track.artist.name],
+        artwork_url: track.album.cover_xl,
+        duration_ms: track.duration.map(|seconds| seconds * 1000),
+        ..Default::default()
+    };
+    SearchCandidate {
+        link: track.link,
+        metadata,
+    }
+}
+
+fn album_candidate(album: DeezerAlbum) -> SearchCandidate {
+    let metadata = MusicMetadata {
+        upc: album.upc,
+        album: Some(album.title.clone()),
+        title: Some(album.title),
+        artists: vec![album.artist.name],
+        artwork_url: album.cover_xl,
+        ..Default::default()
+    };
+    SearchCandidate {
+        link: album.link,
+        metadata,
+    }
+}
+
+#[async_trait]
+impl MusicSearchResolver for DeezerResolver {
+    fn platform(&self) -> MusicPlatform {
+        MusicPlatform::Deezer
+    }
+
+    async fn search(
+        &self,
+        kind: MusicEntityKind,
+        query: &MusicMetadata,
+    ) -> Result<Vec<SearchCandidate>> {
+        match kind {
+            MusicEntityKind::Song => {
+                if let Some(isrc) = &query.isrc
+                    && let Some(track) = self.get_track_by_isrc(isrc).await?
+                {
+                    return Ok(vec![track_candidate(track)]);
+                }
+                let Some(text) = text_query("track", query) else {
+                    return Ok(vec![]);
+                };
+                let tracks: Vec<DeezerTrack> = self.run_search("search", &text).await?;
+                Ok(tracks.into_iter().map(track_candidate).collect())
+            }
+            MusicEntityKind::Album => {
+                if let Some(upc) = &query.upc
+                    && let Some(album) = self.get_album_by_upc(upc).await?
+                {
+                    return Ok(vec![album_candidate(album)]);
+                }
+                let Some(text) = text_query("album", query) else {
+                    return Ok(vec![]);
+                };
+                let albums: Vec<DeezerAlbum> = self.run_search("search/album", &text).await?;
+                Ok(albums.into_iter().map(album_candidate).collect())
+            }
+            MusicEntityKind::Artist | MusicEntityKind::Playlist => Ok(vec![]),
+        }
+    }
+
+    async fn search_text(&self, text: &str) -> Result<Vec<SearchCandidate>> {
+        let tracks: Vec<DeezerTrack> = self.run_search("search", text).await?;
+        Ok(tracks.into_iter().map(track_candidate).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{
+        Json, Router,
+        extract::{Path, Query},
+        routing::get,
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{config::UpstreamConfig, test_server::serve};
+
+    fn error(code: i32, message: &str) -> Value {
+        json!({ "error": { "type": "DataException", "message": message, "code": code } })
+    }
+
+    fn track() -> Value {
+        json!({
+            "id": 3135556,
+            "link": "https://www.deezer.com/track/3135556",
+            "title": "Song",
+            "isrc": "GBDUW0000059",
+            "duration": 224,
+            "artist": { "name": "Artist" },
+            "album": { "title": "Album", "cover_xl": "https://cdn.deezer.com/cover.jpg" }
+        })
+    }
+
+    async fn get_track(Path(id): Path<String>) -> Json<Value> {
+        match id.as_str() {
+            "isrc:GBDUW0000059" => Json(track()),
+            "isrc:QUOTA" => Json(error(4, "Quota limit exceeded")),
+            "isrc:BROKEN" => Json(error(300, "Invalid token")),
+            _ => Json(error(800, "no data")),
+        }
+    }
+
+    async fn search(Query(params): Query<HashMap<String, String>>) -> Json<Value> {
+        assert_eq!(params["q"], "track:\"Song\" artist:\"Artist\"");
+        Json(json!({ "data": [track()], "total": 1 }))
+    }
+
+    async fn resolver() -> DeezerResolver {
+        let router = Router::new()
+            .route("/track/{id}", get(get_track))
+            .route("/search", get(search));
+        let config = DeezerConfig {
+            api_base_url: serve(router).await,
+            upstream: UpstreamConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+        };
+        DeezerResolver::new(Client::new(), config)
+    }
+
+    #[tokio::test]
+    async fn looks_tracks_up_by_isrc() {
+        let track = resolver()
+            .await
+            .get_track_by_isrc("GBDUW0000059")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(track.id, 3135556);
+    }
+
+    #[tokio::test]
+    async fn treats_no_data_as_no_match() {
+        let track = resolver()
+            .await
+            .get_track_by_isrc("USUM71703861")
+            .await
+            .unwrap();
+        assert!(track.is_none());
+    }
+
+    #[tokio::test]
+    async fn maps_the_quota_error_to_rate_limited() {
+        let error = resolver()
+            .await
+            .get_track_by_isrc("QUOTA")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MusicLinkError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn maps_other_errors_to_upstream_unavailable() {
+        let error = resolver()
+            .await
+            .get_track_by_isrc("BROKEN")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, MusicLinkError::UpstreamUnavailable(message) if message.contains("Invalid token"))
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_text_search_and_maps_the_results() {
+        let query = MusicMetadata {
+            isrc: Some("USUM71703861".to_owned()),
+            title: Some("Song".to_owned()),
+            artists: vec!["Artist".to_owned()],
+            ..Default::default()
+        };
+        let candidates = resolver()
+            .await
+            .search(MusicEntityKind::Song, &query)
+            .await
+            .unwrap();
+
+        assert_eq!(candidates.len(), 1);
+        let candidate = &candidates[0];
+        assert_eq!(candidate.link, "https://www.deezer.com/track/3135556");
+        assert_eq!(candidate.metadata.title.as_deref(), Some("Song"));
+        assert_eq!(candidate.metadata.album.as_deref(), Some("Album"));
+        assert_eq!(candidate.metadata.artists, vec!["Artist"]);
+        assert_eq!(candidate.metadata.isrc.as_deref(), Some("GBDUW0000059"));
+        assert_eq!(candidate.metadata.duration_ms, Some(224_000));
+        assert_eq!(
+            candidate.metadata.artwork_url.as_deref(),
+            Some("https://cdn.deezer.com/cover.jpg")
+        );
+    }
+}
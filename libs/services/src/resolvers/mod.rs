@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::{
+    error::Result,
+    models::{MusicEntityKind, MusicLinkInput, MusicMetadata, MusicPlatform},
+};
+
+mod deezer;
+mod song_link;
+mod spotify;
+
+pub use deezer::DeezerResolver;
+pub use song_link::SongLinkResolver;
+pub use spotify::SpotifyResolver;
+
+/// The links a single resolver was able to find for an input link.
+#[derive(Debug, Default)]
+pub struct ResolvedMusicLinks {
+    pub kind: Option<MusicEntityKind>,
+    pub metadata: MusicMetadata,
+    pub links: HashMap<MusicPlatform, String>,
+}
+
+/// A backend that can turn a link from one platform into links on others.
+///
+/// Resolvers are tried in order by `MusicLinkService`, and each platform takes
+/// its link from the first resolver that returned one. `previous` holds what the
+/// resolvers before this one found, so later ones can cross-match on it.
+#[async_trait]
+pub trait MusicResolver: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn resolve(
+        &self,
+        input: &MusicLinkInput,
+        previous: &ResolvedMusicLinks,
+    ) -> Result<ResolvedMusicLinks>;
+}
+
+/// Builds a field filtered search query such as `track:"Title" artist:"Artist"`,
+/// which Deezer and Spotify both understand.
+fn text_query(field: &str, query: &MusicMetadata) -> Option<String> {
+    let title = query.title.as_ref()?.replace('"', "");
+    let mut text = format!("{field}:\"{title}\"");
+    if let Some(artist) = query.artists.first() {
+        text.push_str(&format!(" artist:\"{}\"", artist.replace('"', "")));
+    }
+    Some(text)
+}
+
+/// An entity found by searching a single platform's catalogue.
+#[derive(Debug, Clone)]
+pub struct SearchCandidate {
+    pub link: String,
+    pub metadata: MusicMetadata,
+}
+
+/// A backend that can search one platform for a song or album it has not been
+/// given a link for, used by the matcher to fill platforms resolvers missed.
+#[async_trait]
+pub trait MusicSearchResolver: Send + Sync {
+    fn platform(&self) -> MusicPlatform;
+
+    /// Searches by ISRC or UPC when `query` has one, and by title and artist otherwise.
+    async fn search(
+        &self,
+        kind: MusicEntityKind,
+        query: &MusicMetadata,
+    ) -> Result<Vec<SearchCandidate>>;
+
+    /// Searches songs by free text typed by a user, best match first.
+    async fn search_text(&self, text: &str) -> Result<Vec<SearchCandidate>>;
+}
@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use rust_iso3166::{US, from_alpha2};
+
+use super::{MusicResolver, ResolvedMusicLinks};
+use crate::{
+    canonical::detect_entity_kind,
+    config::SongLinkConfig,
+    error::{MusicLinkError, Result},
+    models::{
+        MusicEntityKind, MusicLinkInput, MusicMetadata, MusicPlatform,
+        providers::{SongLinkPlatform, SongLinkResponse},
+    },
+    upstream::UpstreamGuard,
+    utils::error_for_status,
+};
+
+pub struct SongLinkResolver {
+    client: Client,
+    config: SongLinkConfig,
+    guard: Arc<UpstreamGuard>,
+}
+
+impl SongLinkResolver {
+    pub fn new(client: Client, config: SongLinkConfig) -> Self {
+        let guard = UpstreamGuard::new("song.link", config.upstream.clone());
+        Self::with_guard(client, config, Arc::new(guard))
+    }
+
+    /// Creates a resolver whose requests go through a shared `guard`.
+    pub fn with_guard(client: Client, config: SongLinkConfig, guard: Arc<UpstreamGuard>) -> Self {
+        Self {
+            client,
+            config,
+            guard,
+        }
+    }
+
+    pub fn guard(&self) -> Arc<UpstreamGuard> {
+        self.guard.clone()
+    }
+
+    async fn fetch(&self, url: Url, link: &str) -> Result<SongLinkResponse> {
+        let response = self.client.get(url).send().await?;
+        let response = error_for_status("song.link", response, link)?;
+        response.json::<SongLinkResponse>().await.map_err(|e| {
+            MusicLinkError::UpstreamUnavailable(format!("Invalid song.link response: {e}"))
+        })
+    }
+}
+
+fn to_music_platform(platform: &SongLinkPlatform) -> Option<MusicPlatform> {
+    match platform {
+        SongLinkPlatform::Spotify => Some(MusicPlatform::Spotify),
+        SongLinkPlatform::AppleMusic => Some(MusicPlatform::AppleMusic),
+        SongLinkPlatform::YoutubeMusic => Some(MusicPlatform::YoutubeMusic),
+        SongLinkPlatform::Youtube => Some(MusicPlatform::Youtube),
+        SongLinkPlatform::Deezer => Some(MusicPlatform::Deezer),
+        SongLinkPlatform::Tidal => Some(MusicPlatform::Tidal),
+        SongLinkPlatform::Soundcloud => Some(MusicPlatform::Soundcloud),
+        SongLinkPlatform::AmazonMusic => Some(MusicPlatform::AmazonMusic),
+        SongLinkPlatform::AmazonStore => Some(MusicPlatform::AmazonStore),
+        SongLinkPlatform::Bandcamp => Some(MusicPlatform::Bandcamp),
+        SongLinkPlatform::Pandora => Some(MusicPlatform::Pandora),
+        SongLinkPlatform::Napster => Some(MusicPlatform::Napster),
+        SongLinkPlatform::Audiomack => Some(MusicPlatform::Audiomack),
+        SongLinkPlatform::Anghami => Some(MusicPlatform::Anghami),
+        SongLinkPlatform::Boomplay => Some(MusicPlatform::Boomplay),
+        SongLinkPlatform::Audius => Some(MusicPlatform::Audius),
+        SongLinkPlatform::Yandex => Some(MusicPlatform::Yandex),
+        SongLinkPlatform::Spinrilla => Some(MusicPlatform::Spinrilla),
+        SongLinkPlatform::Itunes => Some(MusicPlatform::Itunes),
+        SongLinkPlatform::Google => Some(MusicPlatform::Google),
+        SongLinkPlatform::GoogleStore => Some(MusicPlatform::GoogleStore),
+        SongLinkPlatform::Unknown(_) => None,
+    }
+}
+
+#[async_trait]
+impl MusicResolver for SongLinkResolver {
+    fn name(&self) -> &'static str {
+        "song.link"
+    }
+
+    async fn resolve(
+        &self,
+        input: &MusicLinkInput,
+        _previous: &ResolvedMusicLinks,
+    ) -> Result<ResolvedMusicLinks> {
+        let user_country = from_alpha2(input.user_country.as_str()).unwrap_or(US);
+
+        let detected_kind = detect_entity_kind(&input.link);
+        if matches!(
+            detected_kind,
+            Some(MusicEntityKind::Artist | MusicEntityKind::Playlist)
+        ) {
+            tracing::debug!("song.link does not support {:?} links", detected_kind);
+            return Ok(ResolvedMusicLinks {
+                kind: detected_kind,
+                ..Default::default()
+            });
+        }
+        let song_if_single = detected_kind != Some(MusicEntityKind::Album);
+
+        let url = Url::parse_with_params(
+            &self.config.api_url,
+            &[
+                (
+                    "songIfSingle",
+                    if song_if_single { "true" } else { "false" },
+                ),
+                ("url", input.link.as_str()),
+                ("userCountry", user_country.alpha2),
+            ],
+        )
+        .map_err(|_| MusicLinkError::UnsupportedUrl(input.link.clone()))?;
+        let response = self
+            .guard
+            .call(|| self.fetch(url.clone(), &input.link))
+            .await?;
+
+        let entity = response
+            .entities_by_unique_id
+            .get(&response.entity_unique_id);
+        let kind = entity
+            .and_then(|entity| entity.kind.as_deref())
+            .and_then(|kind| kind.parse::<MusicEntityKind>().ok())
+            .or(detected_kind);
+        let metadata = entity
+            .map(|entity| MusicMetadata {
+                title: entity.title.clone(),
+                artwork_url: entity.thumbnail_url.clone(),
+                artists: entity.artist_name.clone().into_iter().collect(),
+                ..Default::default()
+            })
+            .unwrap_or_default();
+        let links = response
+            .links_by_platform
+            .into_iter()
+            .filter_map(|(platform, link)| {
+                to_music_platform(&platform).map(|platform| (platform, link.url))
+            })
+            .collect();
+        Ok(ResolvedMusicLinks {
+            kind,
+            links,
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use axum::{Json, Router, extract::Query, http::StatusCode, routing::get};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{config::UpstreamConfig, test_server::serve};
+
+    fn resolver(api_url: String) -> SongLinkResolver {
+        let upstream = UpstreamConfig {
+            max_retries: 0,
+            ..Default::default()
+        };
+        let config = SongLinkConfig { api_url, upstream };
+        SongLinkResolver::new(Client::new(), config)
+    }
+
+    fn input(link: &str) -> MusicLinkInput {
+        MusicLinkInput {
+            link: link.to_owned(),
+            user_country: "DE".to_owned(),
+        }
+    }
+
+    async fn links(Query(params): Query<HashMap<String, String>>) -> Json<Value> {
+        assert_eq!(params["url"], "https://open.spotify.com/track/abc");
+        assert_eq!(params["userCountry"], "DE");
+        assert_eq!(params["songIfSingle"], "true");
+        Json(json!({
+            "pageUrl": "https://song.link/s/abc",
+            "entityUniqueId": "SPOTIFY_SONG::abc",
+            "entitiesByUniqueId": {
+                "SPOTIFY_SONG::abc": {
+                    "id": "abc",
+                    "type": "song",
+                    "title": "Song",
+                    "artistName": "Artist",
+                    "thumbnailUrl": "https://i.scdn.co/image/abc",
+                    "platforms": ["spotify"]
+                }
+            },
+            "linksByPlatform": {
+                "spotify": { "url": "https://open.spotify.com/track/abc" },
+                "appleMusic": { "url": "https://music.apple.com/de/song/1" },
+                "someNewPlatform": { "url": "https://example.com/abc" }
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn resolves_links_from_the_configured_api_url() {
+        let base_url = serve(Router::new().route("/links", get(links))).await;
+        let resolved = resolver(format!("{base_url}/links"))
+            .resolve(
+                &input("https://open.spotify.com/track/abc"),
+                &ResolvedMusicLinks::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resolved.kind, Some(MusicEntityKind::Song));
+        assert_eq!(resolved.metadata.title.as_deref(), Some("Song"));
+        assert_eq!(resolved.metadata.artists, vec!["Artist".to_owned()]);
+        assert_eq!(resolved.links.len(), 2);
+        assert_eq!(
+            resolved.links[&MusicPlatform::AppleMusic],
+            "https://music.apple.com/de/song/1"
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_rate_limited_responses() {
+        let router = Router::new().route(
+            "/links",
+            get(|| async { (StatusCode::TOO_MANY_REQUESTS, [("retry-after", "7")]) }),
+        );
+        let base_url = serve(router).await;
+        let error = resolver(format!("{base_url}/links"))
+            .resolve(
+                &input("https://open.spotify.com/track/abc"),
+                &ResolvedMusicLinks::default(),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            MusicLinkError::RateLimited {
+                retry_after: Some(retry_after)
+            } if retry_after == Duration::from_secs(7)
+        ));
+    }
+}
@@ -0,0 +1,515 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use reqwest::{
+    Client, StatusCode, Url,
+    header::{CONTENT_TYPE, HeaderValue},
+};
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+
+use super::{MusicResolver, MusicSearchResolver, ResolvedMusicLinks, SearchCandidate, text_query};
+use crate::{
+    canonical::{detect_entity_kind, detect_platform},
+    config::SpotifyConfig,
+    error::{MusicLinkError, Result},
+    models::{
+        MusicEntityKind, MusicLinkInput, MusicMetadata, MusicPlatform,
+        providers::{
+            SpotifyAlbum, SpotifyArtist, SpotifySearchResponse, SpotifyTokenResponse, SpotifyTrack,
+        },
+    },
+    upstream::UpstreamGuard,
+    utils::error_for_status,
+};
+
+/// Tokens are refreshed this long before Spotify says they expire.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+struct AccessToken {
+    value: String,
+    expires_at: Instant,
+}
+
+/// Looks up tracks and albums through the Spotify Web API.
+///
+/// Spotify links are read directly, and links from other platforms are matched
+/// by the ISRC an earlier resolver found for them. It can also search Spotify
+/// for the matcher.
+pub struct SpotifyResolver {
+    client: Client,
+    config: SpotifyConfig,
+    guard: Arc<UpstreamGuard>,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl SpotifyResolver {
+    pub fn new(client: Client, config: SpotifyConfig) -> Self {
+        let guard = UpstreamGuard::new("spotify", config.upstream.clone());
+        Self::with_guard(client, config, Arc::new(guard))
+    }
+
+    pub fn guard(&self) -> Arc<UpstreamGuard> {
+        self.guard.clone()
+    }
+
+    /// Creates a resolver whose requests go through a shared `guard`.
+    pub fn with_guard(client: Client, config: SpotifyConfig, guard: Arc<UpstreamGuard>) -> Self {
+        Self {
+            client,
+            config,
+            guard,
+            token: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        let url = format!(
+            "{}/api/token",
+            self.config.accounts_base_url.trim_end_matches('/')
+        );
+        let response = self
+            .client
+            .post(url)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .header(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            )
+            .body("grant_type=client_credentials")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(MusicLinkError::UpstreamUnavailable(format!(
+                "Spotify token request responded with {}",
+                response.status()
+            )));
+        }
+        let token = response.json::<SpotifyTokenResponse>().await.map_err(|e| {
+            MusicLinkError::UpstreamUnavailable(format!("Invalid Spotify token response: {e}"))
+        })?;
+        let expires_in = Duration::from_secs(token.expires_in).saturating_sub(TOKEN_EXPIRY_MARGIN);
+        Ok(AccessToken {
+            value: token.access_token,
+            expires_at: Instant::now() + expires_in,
+        })
+    }
+
+    /// Returns a valid client credentials token, requesting a new one when needed.
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(token) = token.as_ref()
+            && token.expires_at > Instant::now()
+        {
+            return Ok(token.value.clone());
+        }
+        let fresh = self.fetch_token().await?;
+        let value = fresh.value.clone();
+        *token = Some(fresh);
+        Ok(value)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url, link: &str) -> Result<T> {
+        let token = self.access_token().await?;
+        let response = self.client.get(url).bearer_auth(token).send().await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            // The token was revoked early, so the next attempt fetches another one.
+            self.token.lock().await.take();
+        }
+        let response = error_for_status("spotify", response, link)?;
+        response.json::<T>().await.map_err(|e| {
+            MusicLinkError::UpstreamUnavailable(format!("Invalid Spotify response: {e}"))
+        })
+    }
+
+    fn api_url(&self, path: &str) -> Result<Url> {
+        let url = format!("{}/{path}", self.config.api_base_url.trim_end_matches('/'));
+        Url::parse(&url).map_err(|e| MusicLinkError::UpstreamUnavailable(e.to_string()))
+    }
+
+    pub async fn get_track(&self, id: &str) -> Result<SpotifyTrack> {
+        let url = self.api_url(&format!("tracks/{id}"))?;
+        self.guard.call(|| self.get(url.clone(), id)).await
+    }
+
+    pub async fn get_album(&self, id: &str) -> Result<SpotifyAlbum> {
+        let url = self.api_url(&format!("albums/{id}"))?;
+        self.guard.call(|| self.get(url.clone(), id)).await
+    }
+
+    /// Runs a search query such as `isrc:USUM71703861` for a single item type.
+    async fn search_items(&self, query: &str, item_type: &str) -> Result<SpotifySearchResponse> {
+        let url = self.api_url("search")?;
+        let url = Url::parse_with_params(url.as_str(), &[("q", query), ("type", item_type)])
+            .map_err(|e| MusicLinkError::UpstreamUnavailable(e.to_string()))?;
+        self.guard.call(|| self.get(url.clone(), query)).await
+    }
+
+    /// Finds the tracks Spotify has for a given ISRC, best match first.
+    pub async fn search_by_isrc(&self, isrc: &str) -> Result<Vec<SpotifyTrack>> {
+        let response = self.search_items(&format!("isrc:{isrc}"), "track").await?;
+        Ok(response.tracks.map(|page| page.items).unwrap_or_default())
+    }
+
+    /// Finds the albums Spotify has for a given UPC, best match first.
+    pub async fn search_by_upc(&self, upc: &str) -> Result<Vec<SpotifyAlbum>> {
+        let response = self.search_items(&format!("upc:{upc}"), "album").await?;
+        Ok(response.albums.map(|page| page.items).unwrap_or_default())
+    }
+}
+
+fn artist_names(artists: Vec<SpotifyArtist>) -> Vec<String> {
+    artists.into_iter().map(|artist| artist.name).collect()
+}
+
+fn spotify_link(kind: &str, id: &str, external_url: Option<String>) -> String {
+    external_url.unwrap_or_else(|| format!("https://open.spotify.com/{kind}/{id}"))
+}
+
+fn track_candidate(track: SpotifyTrack) -> SearchCandidate {
+    let link = spotify_link("track", &track.id, track.external_urls.spotify);
+    let metadata = MusicMetadata {
+        isrc: track.external_ids.isrc,
+        album: Some(track.album.name),
+        title: Some(track.name),
+        artists: artist_names(track.artists),
+        artwork_url: track.album.images.into_iter().next().map(|image| image.url),
+        duration_ms: Some(track.duration_ms),
+        ..Default::default()
+    };
+    SearchCandidate { link, metadata }
+}
+
+fn album_candidate(album: SpotifyAlbum) -> SearchCandidate {
+    let link = spotify_link("album", &album.id, album.external_urls.spotify);
+    let metadata = MusicMetadata {
+        upc: album.external_ids.upc,
+        album: Some(album.name.clone()),
+        title: Some(album.name),
+        artists: artist_names(album.artists),
+        artwork_url: album.images.into_iter().next().map(|image| image.url),
+        ..Default::default()
+    };
+    SearchCandidate { link, metadata }
+}
+
+fn resolved_from_candidate(
+    kind: MusicEntityKind,
+    candidate: SearchCandidate,
+) -> ResolvedMusicLinks {
+    ResolvedMusicLinks {
+        kind: Some(kind),
+        metadata: candidate.metadata,
+        links: [(MusicPlatform::Spotify, candidate.link)].into(),
+    }
+}
+
+/// Reads the entity kind and id out of a canonical `open.spotify.com` link.
+fn spotify_id(link: &str) -> Option<(MusicEntityKind, String)> {
+    if detect_platform(link) != Some(MusicPlatform::Spotify) {
+        return None;
+    }
+    let kind = detect_entity_kind(link)?;
+    let url = Url::parse(link).ok()?;
+    let id = url.path_segments()?.filter(|s| !s.is_empty()).nth(1)?;
+    Some((kind, id.to_owned()))
+}
+
+#[async_trait]
+impl MusicResolver for SpotifyResolver {
+    fn name(&self) -> &'static str {
+        "spotify"
+    }
+
+    async fn resolve(
+        &self,
+        input: &MusicLinkInput,
+        previous: &ResolvedMusicLinks,
+    ) -> Result<ResolvedMusicLinks> {
+        let spotify_link = spotify_id(&input.link).or_else(|| {
+            previous
+                .links
+                .get(&MusicPlatform::Spotify)
+                .and_then(|link| spotify_id(link))
+        });
+        match spotify_link {
+            Some((kind @ MusicEntityKind::Song, id)) => {
+                let track = self.get_track(&id).await?;
+                Ok(resolved_from_candidate(kind, track_candidate(track)))
+            }
+            Some((kind @ MusicEntityKind::Album, id)) => {
+                let album = self.get_album(&id).await?;
+                Ok(resolved_from_candidate(kind, album_candidate(album)))
+            }
+            Some((kind, _)) => {
+                tracing::debug!("Spotify lookups do not support {:?} links", kind);
+                Ok(ResolvedMusicLinks::default())
+            }
+            None => match &previous.metadata.isrc {
+                Some(isrc) => {
+                    let tracks = self.search_by_isrc(isrc).await?;
+                    Ok(tracks
+                        .into_iter()
+                        .next()
+                        .map(|track| {
+                            resolved_from_candidate(MusicEntityKind::Song, track_candidate(track))
+                        })
+                        .unwrap_or_default())
+                }
+                None => Ok(ResolvedMusicLinks::default()),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl MusicSearchResolver for SpotifyResolver {
+    fn platform(&self) -> MusicPlatform {
+        MusicPlatform::Spotify
+    }
+
+    async fn search(
+        &self,
+        kind: MusicEntityKind,
+        query: &MusicMetadata,
+    ) -> Result<Vec<SearchCandidate>> {
+        match kind {
+            MusicEntityKind::Song => {
+                if let Some(isrc) = &query.isrc {
+                    let tracks = self.search_by_isrc(isrc).await?;
+                    if !tracks.is_empty() {
+                        return Ok(tracks.into_iter().map(track_candidate).collect());
+                    }
+                }
+                let Some(text) = text_query("track", query) else {
+                    return Ok(vec![]);
+                };
+                let response = self.search_items(&text, "track").await?;
+                let tracks = response.tracks.map(|page| page.items).unwrap_or_default();
+                Ok(tracks.into_iter().map(track_candidate).collect())
+            }
+            MusicEntityKind::Album => {
+                if let Some(upc) = &query.upc {
+                    let albums = self.search_by_upc(upc).await?;
+                    if !albums.is_empty() {
+                        // Search results omit external ids, but the query only
+                        // matches albums with this exact UPC.
+                        return Ok(albums
+                            .into_iter()
+                            .map(|album| {
+                                let mut candidate = album_candidate(album);
+                                candidate.metadata.upc = Some(upc.clone());
+                                candidate
+                            })
+                            .collect());
+                    }
+                }
+                let Some(text) = text_query("album", query) else {
+                    return Ok(vec![]);
+                };
+                let response = self.search_items(&text, "album").await?;
+                let albums = response.albums.map(|page| page.items).unwrap_or_default();
+                Ok(albums.into_iter().map(album_candidate).collect())
+            }
+            MusicEntityKind::Artist | MusicEntityKind::Playlist => Ok(vec![]),
+        }
+    }
+
+    async fn search_text(&self, text: &str) -> Result<Vec<SearchCandidate>> {
+        let response = self.search_items(text, "track").await?;
+        let tracks = response.tracks.map(|page| page.items).unwrap_or_default();
+        Ok(tracks.into_iter().map(track_candidate).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use axum::{
+        Json, Router,
+        extract::{Path, Query, State},
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{config::UpstreamConfig, test_server::serve};
+
+    #[derive(Default)]
+    struct Stub {
+        tokens_issued: AtomicUsize,
+        /// Bearer tokens the API rejects with a 401.
+        revoked: Vec<String>,
+    }
+
+    async fn token(State(stub): State<Arc<Stub>>, headers: HeaderMap, body: String) -> Json<Value> {
+        // "id:secret" in base64.
+        assert_eq!(headers["authorization"], "Basic aWQ6c2VjcmV0");
+        assert_eq!(body, "grant_type=client_credentials");
+        let issued = stub.tokens_issued.fetch_add(1, Ordering::SeqCst) + 1;
+        Json(json!({ "access_token": format!("token-{issued}"), "expires_in": 3600 }))
+    }
+
+    fn track(id: &str) -> Value {
+        json!({
+            "id": id,
+            "name": "Song",
+            "duration_ms": 201000,
+            "artists": [{ "name": "Artist" }, { "name": "Guest" }],
+            "album": { "name": "Album", "images": [{ "url": "https://i.scdn.co/image/big" }] },
+            "external_ids": { "isrc": "USUM71703861" },
+            "external_urls": { "spotify": format!("https://open.spotify.com/track/{id}") }
+        })
+    }
+
+    async fn get_track(
+        State(stub): State<Arc<Stub>>,
+        Path(id): Path<String>,
+        headers: HeaderMap,
+    ) -> std::result::Result<Json<Value>, StatusCode> {
+        let bearer = headers["authorization"].to_str().unwrap();
+        if stub
+            .revoked
+            .iter()
+            .any(|token| bearer == format!("Bearer {token}"))
+        {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(Json(track(&id)))
+    }
+
+    async fn search(Query(params): Query<HashMap<String, String>>) -> Json<Value> {
+        match params["type"].as_str() {
+            "track" => {
+                assert_eq!(params["q"], "isrc:USUM71703861");
+                Json(json!({ "tracks": { "items": [track("abc")] } }))
+            }
+            _ => {
+                assert_eq!(params["q"], "upc:00602567890123");
+                Json(json!({
+                    "albums": {
+                        "items": [{
+                            "id": "xyz",
+                            "name": "Album",
+                            "artists": [{ "name": "Artist" }],
+                            "images": []
+                        }]
+                    }
+                }))
+            }
+        }
+    }
+
+    async fn resolver(stub: Stub) -> (SpotifyResolver, Arc<Stub>) {
+        let stub = Arc::new(stub);
+        let router = Router::new()
+            .route("/api/token", post(token))
+            .route("/v1/tracks/{id}", get(get_track))
+            .route("/v1/search", get(search))
+            .with_state(stub.clone());
+        let base_url = serve(router).await;
+        let mut config = SpotifyConfig::new("id", "secret");
+        config.api_base_url = format!("{base_url}/v1");
+        config.accounts_base_url = base_url;
+        config.upstream = UpstreamConfig {
+            max_retries: 1,
+            base_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        (SpotifyResolver::new(Client::new(), config), stub)
+    }
+
+    #[tokio::test]
+    async fn reuses_the_client_credentials_token() {
+        let (resolver, stub) = resolver(Stub::default()).await;
+        resolver.get_track("abc").await.unwrap();
+        resolver.get_track("def").await.unwrap();
+        assert_eq!(stub.tokens_issued.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn fetches_a_new_token_after_a_401() {
+        let stub = Stub {
+            revoked: vec!["token-1".to_owned()],
+            ..Default::default()
+        };
+        let (resolver, stub) = resolver(stub).await;
+        let track = resolver.get_track("abc").await.unwrap();
+        assert_eq!(track.id, "abc");
+        assert_eq!(stub.tokens_issued.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn maps_track_search_results_to_candidates() {
+        let (resolver, _) = resolver(Stub::default()).await;
+        let query = MusicMetadata {
+            isrc: Some("USUM71703861".to_owned()),
+            ..Default::default()
+        };
+        let candidates = resolver
+            .search(MusicEntityKind::Song, &query)
+            .await
+            .unwrap();
+
+        assert_eq!(candidates.len(), 1);
+        let candidate = &candidates[0];
+        assert_eq!(candidate.link, "https://open.spotify.com/track/abc");
+        assert_eq!(candidate.metadata.title.as_deref(), Some("Song"));
+        assert_eq!(candidate.metadata.album.as_deref(), Some("Album"));
+        assert_eq!(candidate.metadata.artists, vec!["Artist", "Guest"]);
+        assert_eq!(candidate.metadata.isrc.as_deref(), Some("USUM71703861"));
+        assert_eq!(candidate.metadata.duration_ms, Some(201000));
+        assert_eq!(
+            candidate.metadata.artwork_url.as_deref(),
+            Some("https://i.scdn.co/image/big")
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_the_queried_upc_on_album_search_results() {
+        let (resolver, _) = resolver(Stub::default()).await;
+        let query = MusicMetadata {
+            upc: Some("00602567890123".to_owned()),
+            ..Default::default()
+        };
+        let candidates = resolver
+            .search(MusicEntityKind::Album, &query)
+            .await
+            .unwrap();
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].link, "https://open.spotify.com/album/xyz");
+        assert_eq!(
+            candidates[0].metadata.upc.as_deref(),
+            Some("00602567890123")
+        );
+        assert_eq!(candidates[0].metadata.artwork_url, None);
+    }
+
+    #[tokio::test]
+    async fn resolves_spotify_links_by_their_id() {
+        let (resolver, _) = resolver(Stub::default()).await;
+        let input = MusicLinkInput {
+            link: "https://open.spotify.com/track/abc".to_owned(),
+            user_country: "US".to_owned(),
+        };
+        let resolved = resolver
+            .resolve(&input, &ResolvedMusicLinks::default())
+            .await
+            .unwrap();
+
+        assert_eq!(resolved.kind, Some(MusicEntityKind::Song));
+        assert_eq!(
+            resolved.links[&MusicPlatform::Spotify],
+            "https://open.spotify.com/track/abc"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use schematic::Config;
+use serde::Serialize;
+
+use crate::config::{DeezerConfig, MusicLinkServiceConfig, SpotifyConfig, UpstreamConfig};
+
+/// Environment settings for `MusicLinkService`, shared by every app so they
+/// build the service the same way. Unset values keep the service defaults.
+#[derive(Debug, Clone, Serialize, Config)]
+#[config(env)]
+pub struct MusicLinkSettings {
+    #[setting(env = "MUSIC_LINK_USER_AGENT")]
+    pub user_agent: Option<String>,
+    #[setting(env = "MUSIC_LINK_REQUEST_TIMEOUT_SECONDS")]
+    pub request_timeout_seconds: Option<u64>,
+    #[setting(env = "MUSIC_LINK_CACHE_CAPACITY")]
+    pub cache_capacity: Option<usize>,
+    #[setting(env = "MUSIC_LINK_CACHE_TTL_SECONDS")]
+    pub cache_ttl_seconds: Option<u64>,
+    #[setting(env = "SONG_LINK_API_URL")]
+    pub song_link_api_url: Option<String>,
+    #[setting(env = "SONG_LINK_REQUESTS_PER_MINUTE")]
+    pub song_link_requests_per_minute: Option<u32>,
+    #[setting(env = "SONG_LINK_MAX_QUEUE_WAIT_SECONDS")]
+    pub song_link_max_queue_wait_seconds: Option<u64>,
+    #[setting(env = "SONG_LINK_MAX_RETRIES")]
+    pub song_link_max_retries: Option<u32>,
+    #[setting(env = "SONG_LINK_FAILURE_THRESHOLD")]
+    pub song_link_failure_threshold: Option<u32>,
+    #[setting(env = "SONG_LINK_CIRCUIT_OPEN_SECONDS")]
+    pub song_link_circuit_open_seconds: Option<u64>,
+    #[setting(env = "SPOTIFY_CLIENT_ID")]
+    pub spotify_client_id: Option<String>,
+    #[setting(env = "SPOTIFY_CLIENT_SECRET")]
+    pub spotify_client_secret: Option<String>,
+    #[setting(env = "SPOTIFY_API_BASE_URL")]
+    pub spotify_api_base_url: Option<String>,
+    #[setting(env = "SPOTIFY_ACCOUNTS_BASE_URL")]
+    pub spotify_accounts_base_url: Option<String>,
+    #[setting(env = "SPOTIFY_REQUESTS_PER_MINUTE")]
+    pub spotify_requests_per_minute: Option<u32>,
+    #[setting(default = true, env = "DEEZER_ENABLED")]
+    pub deezer_enabled: bool,
+    #[setting(env = "DEEZER_API_BASE_URL")]
+    pub deezer_api_base_url: Option<String>,
+    #[setting(env = "DEEZER_REQUESTS_PER_MINUTE")]
+    pub deezer_requests_per_minute: Option<u32>,
+}
+
+fn override_requests_per_minute(upstream: &mut UpstreamConfig, requests_per_minute: Option<u32>) {
+    if let Some(requests_per_minute) = requests_per_minute {
+        upstream.requests_per_minute = requests_per_minute;
+    }
+}
+
+impl MusicLinkSettings {
+    pub fn service_config(&self) -> MusicLinkServiceConfig {
+        let mut config = MusicLinkServiceConfig::default();
+        if let Some(user_agent) = &self.user_agent {
+            config.user_agent = user_agent.clone();
+        }
+        if let Some(seconds) = self.request_timeout_seconds {
+            config.request_timeout = Duration::from_secs(seconds);
+        }
+        if let Some(capacity) = self.cache_capacity {
+            config.cache.capacity = capacity;
+        }
+        if let Some(seconds) = self.cache_ttl_seconds {
+            config.cache.ttl = Duration::from_secs(seconds);
+        }
+        if let Some(url) = &self.song_link_api_url {
+            config.song_link.api_url = url.clone();
+        }
+        let song_link = &mut config.song_link.upstream;
+        override_requests_per_minute(song_link, self.song_link_requests_per_minute);
+        if let Some(seconds) = self.song_link_max_queue_wait_seconds {
+            song_link.max_queue_wait = Duration::from_secs(seconds);
+        }
+        if let Some(retries) = self.song_link_max_retries {
+            song_link.max_retries = retries;
+        }
+        if let Some(threshold) = self.song_link_failure_threshold {
+            song_link.failure_threshold = threshold;
+        }
+        if let Some(seconds) = self.song_link_circuit_open_seconds {
+            song_link.open_duration = Duration::from_secs(seconds);
+        }
+        config.spotify = match (&self.spotify_client_id, &self.spotify_client_secret) {
+            (Some(client_id), Some(client_secret)) => {
+                let mut spotify = SpotifyConfig::new(client_id, client_secret);
+                if let Some(url) = &self.spotify_api_base_url {
+                    spotify.api_base_url = url.clone();
+                }
+                if let Some(url) = &self.spotify_accounts_base_url {
+                    spotify.accounts_base_url = url.clone();
+                }
+                override_requests_per_minute(
+                    &mut spotify.upstream,
+                    self.spotify_requests_per_minute,
+                );
+                Some(spotify)
+            }
+            _ => None,
+        };
+        config.deezer = self.deezer_enabled.then(|| {
+            let mut deezer = DeezerConfig::default();
+            if let Some(url) = &self.deezer_api_base_url {
+                deezer.api_base_url = url.clone();
+            }
+            override_requests_per_minute(&mut deezer.upstream, self.deezer_requests_per_minute);
+            deezer
+        });
+        config
+    }
+}
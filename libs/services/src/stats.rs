@@ -0,0 +1,638 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use entities::{
+    music_link::Column as MusicLinkColumn,
+    prelude::{
+        MusicLink, TelegramBotChannel, TelegramBotMusicShare, TelegramBotMusicShareReaction,
+        TelegramBotUser,
+    },
+    telegram_bot_channel::Column as ChannelColumn,
+    telegram_bot_music_share::Column as ShareColumn,
+    telegram_bot_music_share_reaction::{Column as ReactionColumn, SentimentResponseMood},
+    telegram_bot_user::Column as UserColumn,
+};
+use sea_orm::{
+    ActiveEnum, ConnectionTrait, DatabaseConnection, FromQueryResult,
+    sea_query::{
+        Alias, Asterisk, Condition, Expr, Func, Order, Query, SelectStatement, SimpleExpr,
+    },
+};
+use uuid::Uuid;
+
+use crate::error::Result;
+
+/// Reactions the sentiment analysis found to be about the music.
+const RATED_MOODS: [SentimentResponseMood; 3] = [
+    SentimentResponseMood::Positive,
+    SentimentResponseMood::Negative,
+    SentimentResponseMood::Neutral,
+];
+
+/// Shares and the reactions they received, of a user or a track.
+#[derive(Debug, Default, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct ShareCounts {
+    pub shares: i64,
+    pub reactions: i64,
+    pub positive_reactions: i64,
+    pub negative_reactions: i64,
+    /// Reactions the sentiment analysis found to be about the music.
+    pub rated_reactions: i64,
+}
+
+impl ShareCounts {
+    /// Share of rated reactions that were positive, from 0.0 to 1.0.
+    pub fn positive_ratio(&self) -> f64 {
+        if self.rated_reactions == 0 {
+            return 0.0;
+        }
+        self.positive_reactions as f64 / self.rated_reactions as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct SharerStats {
+    pub telegram_user_id: i64,
+    #[sea_orm(nested)]
+    pub counts: ShareCounts,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct TrackStats {
+    pub music_link_id: Uuid,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    #[sea_orm(nested)]
+    pub counts: ShareCounts,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct ArtistStats {
+    pub artist: String,
+    pub shares: i64,
+}
+
+/// A single share and the reactions it received.
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct ShareStats {
+    pub telegram_user_id: i64,
+    pub shared_at: DateTime<Utc>,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub reactions: i64,
+}
+
+/// The longest run of consecutive days with at least one share.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareStreak {
+    pub days: i64,
+    pub first_day: NaiveDate,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ShareDay {
+    day: NaiveDate,
+}
+
+/// The shares a stats query looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ShareFilter {
+    pub telegram_channel_id: i64,
+    /// Only shares by this user, instead of everyone in the chat.
+    pub telegram_user_id: Option<i64>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ShareFilter {
+    fn chat(telegram_channel_id: i64, since: Option<DateTime<Utc>>) -> Self {
+        Self {
+            telegram_channel_id,
+            telegram_user_id: None,
+            since,
+            until: None,
+        }
+    }
+}
+
+fn share(column: ShareColumn) -> Expr {
+    Expr::col((TelegramBotMusicShare, column))
+}
+
+fn reaction(column: ReactionColumn) -> Expr {
+    Expr::col((TelegramBotMusicShareReaction, column))
+}
+
+fn music_link(column: MusicLinkColumn) -> Expr {
+    Expr::col((MusicLink, column))
+}
+
+fn user(column: UserColumn) -> Expr {
+    Expr::col((TelegramBotUser, column))
+}
+
+fn channel(column: ChannelColumn) -> Expr {
+    Expr::col((TelegramBotChannel, column))
+}
+
+/// Joins every share to its music link and the chat it was made in, along with
+/// the reactions other users left on it. Reactions by the sharer themselves
+/// are not counted.
+fn shares_with_reactions(filter: &ShareFilter) -> SelectStatement {
+    Query::select()
+        .from(TelegramBotMusicShare)
+        .inner_join(
+            MusicLink,
+            music_link(MusicLinkColumn::Id)
+                .equals((TelegramBotMusicShare, ShareColumn::MusicLinkId)),
+        )
+        .inner_join(
+            TelegramBotUser,
+            user(UserColumn::Id).equals((TelegramBotMusicShare, ShareColumn::TelegramBotUserId)),
+        )
+        .inner_join(
+            TelegramBotChannel,
+            channel(ChannelColumn::Id).equals((TelegramBotUser, UserColumn::TelegramBotChannelId)),
+        )
+        .left_join(
+            TelegramBotMusicShareReaction,
+            Condition::all()
+                .add(
+                    reaction(ReactionColumn::TelegramBotMusicShareId)
+                        .equals((TelegramBotMusicShare, ShareColumn::Id)),
+                )
+                .add(
+                    reaction(ReactionColumn::TelegramBotUserId)
+                        .not_equals((TelegramBotMusicShare, ShareColumn::TelegramBotUserId)),
+                ),
+        )
+        .and_where(channel(ChannelColumn::TelegramChannelId).eq(filter.telegram_channel_id))
+        .and_where_option(
+            filter
+                .since
+                .map(|since| share(ShareColumn::CreatedAt).gte(since)),
+        )
+        .and_where_option(
+            filter
+                .until
+                .map(|until| share(ShareColumn::CreatedAt).lt(until)),
+        )
+        .and_where_option(
+            filter
+                .telegram_user_id
+                .map(|id| user(UserColumn::TelegramUserId).eq(id)),
+        )
+        .to_owned()
+}
+
+fn shares() -> SimpleExpr {
+    Func::count_distinct(share(ShareColumn::Id)).into()
+}
+
+fn reactions() -> SimpleExpr {
+    Func::count(reaction(ReactionColumn::Id)).into()
+}
+
+/// Counts the reactions whose sentiment is one of `moods`.
+fn reactions_with_mood(moods: &[SentimentResponseMood]) -> SimpleExpr {
+    let is_mood = reaction(ReactionColumn::LlmSentimentAnalysis)
+        .is_in(moods.iter().map(ActiveEnum::to_value));
+    Func::count(Expr::case(is_mood, reaction(ReactionColumn::Id))).into()
+}
+
+fn positive_reactions() -> SimpleExpr {
+    reactions_with_mood(&[SentimentResponseMood::Positive])
+}
+
+fn negative_reactions() -> SimpleExpr {
+    reactions_with_mood(&[SentimentResponseMood::Negative])
+}
+
+fn rated_reactions() -> SimpleExpr {
+    reactions_with_mood(&RATED_MOODS)
+}
+
+/// Selects the columns of `ShareCounts`.
+fn select_share_counts(query: &mut SelectStatement) -> &mut SelectStatement {
+    query
+        .expr_as(shares(), Alias::new("shares"))
+        .expr_as(reactions(), Alias::new("reactions"))
+        .expr_as(positive_reactions(), Alias::new("positive_reactions"))
+        .expr_as(negative_reactions(), Alias::new("negative_reactions"))
+        .expr_as(rated_reactions(), Alias::new("rated_reactions"))
+}
+
+/// Shares and their reactions grouped by the user who shared them.
+fn sharers(filter: &ShareFilter) -> SelectStatement {
+    let mut query = shares_with_reactions(filter);
+    select_share_counts(&mut query)
+        .column((TelegramBotUser, UserColumn::TelegramUserId))
+        .group_by_col((TelegramBotUser, UserColumn::TelegramUserId));
+    query
+}
+
+/// Shares and their reactions grouped by the track that was shared.
+fn tracks(filter: &ShareFilter) -> SelectStatement {
+    let mut query = shares_with_reactions(filter);
+    select_share_counts(&mut query)
+        .expr_as(music_link(MusicLinkColumn::Id), Alias::new("music_link_id"))
+        .column((MusicLink, MusicLinkColumn::Title))
+        .column((MusicLink, MusicLinkColumn::Artists))
+        .group_by_col((MusicLink, MusicLinkColumn::Id));
+    query
+}
+
+async fn query_all<T: FromQueryResult>(
+    query: &SelectStatement,
+    db: &DatabaseConnection,
+) -> Result<Vec<T>> {
+    let statement = db.get_database_backend().build(query);
+    Ok(T::find_by_statement(statement).all(db).await?)
+}
+
+/// The longest run of consecutive days among `days`, the earliest one on ties.
+fn longest_streak(mut days: Vec<NaiveDate>) -> Option<ShareStreak> {
+    days.sort_unstable();
+    days.dedup();
+    let mut longest: Option<ShareStreak> = None;
+    let mut current: Option<ShareStreak> = None;
+    for day in days {
+        current = match current {
+            Some(streak) if (day - streak.first_day).num_days() == streak.days => {
+                Some(ShareStreak {
+                    days: streak.days + 1,
+                    ..streak
+                })
+            }
+            _ => Some(ShareStreak {
+                days: 1,
+                first_day: day,
+            }),
+        };
+        if longest.as_ref().map(|streak| streak.days) < current.as_ref().map(|s| s.days) {
+            longest = current.clone();
+        }
+    }
+    longest
+}
+
+pub(crate) async fn share_counts(
+    filter: &ShareFilter,
+    db: &DatabaseConnection,
+) -> Result<ShareCounts> {
+    let mut query = shares_with_reactions(filter);
+    select_share_counts(&mut query);
+    let mut rows = query_all::<ShareCounts>(&query, db).await?;
+    Ok(rows.pop().unwrap_or_default())
+}
+
+pub(crate) async fn sharers_by_shares(
+    filter: &ShareFilter,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<SharerStats>> {
+    let mut query = sharers(filter);
+    query
+        .order_by_expr(shares(), Order::Desc)
+        .order_by_expr(positive_reactions(), Order::Desc)
+        .limit(limit);
+    query_all(&query, db).await
+}
+
+pub(crate) async fn tracks_by_positive_reactions(
+    filter: &ShareFilter,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<TrackStats>> {
+    let mut query = tracks(filter);
+    query
+        .and_having(Expr::expr(positive_reactions()).gt(0))
+        .order_by_expr(positive_reactions(), Order::Desc)
+        .order_by_expr(reactions(), Order::Desc)
+        .limit(limit);
+    query_all(&query, db).await
+}
+
+/// Artists ordered by how many shares credit them.
+pub(crate) async fn artists_by_shares(
+    filter: &ShareFilter,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<ArtistStats>> {
+    let artist = Alias::new("artist");
+    let shared = shares_with_reactions(filter)
+        .distinct()
+        .column((TelegramBotMusicShare, ShareColumn::Id))
+        .expr_as(
+            Func::cust(Alias::new("unnest")).arg(music_link(MusicLinkColumn::Artists)),
+            artist.clone(),
+        )
+        .to_owned();
+    let query = Query::select()
+        .column(artist.clone())
+        .expr_as(Func::count(Expr::col(Asterisk)), Alias::new("shares"))
+        .from_subquery(shared, Alias::new("shared"))
+        .group_by_col(artist.clone())
+        .order_by_expr(Func::count(Expr::col(Asterisk)).into(), Order::Desc)
+        .order_by(artist, Order::Asc)
+        .limit(limit)
+        .to_owned();
+    query_all(&query, db).await
+}
+
+/// Shares ordered by how many reactions they received, leaving out those
+/// that received none.
+pub(crate) async fn shares_by_reactions(
+    filter: &ShareFilter,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<ShareStats>> {
+    let query = shares_with_reactions(filter)
+        .column((TelegramBotUser, UserColumn::TelegramUserId))
+        .expr_as(share(ShareColumn::CreatedAt), Alias::new("shared_at"))
+        .column((MusicLink, MusicLinkColumn::Title))
+        .column((MusicLink, MusicLinkColumn::Artists))
+        .expr_as(reactions(), Alias::new("reactions"))
+        .group_by_col((TelegramBotMusicShare, ShareColumn::Id))
+        .group_by_col((TelegramBotUser, UserColumn::TelegramUserId))
+        .group_by_col((MusicLink, MusicLinkColumn::Id))
+        .and_having(Expr::expr(reactions()).gt(0))
+        .order_by_expr(reactions(), Order::Desc)
+        .order_by((TelegramBotMusicShare, ShareColumn::CreatedAt), Order::Asc)
+        .limit(limit)
+        .to_owned();
+    query_all(&query, db).await
+}
+
+/// The longest streak of days with shares, counting days in `timezone`.
+pub(crate) async fn longest_share_streak(
+    filter: &ShareFilter,
+    timezone: &str,
+    db: &DatabaseConnection,
+) -> Result<Option<ShareStreak>> {
+    let day = Func::cust(Alias::new("timezone"))
+        .arg(timezone)
+        .arg(share(ShareColumn::CreatedAt));
+    let query = shares_with_reactions(filter)
+        .distinct()
+        .expr_as(Func::cast_as(day, Alias::new("date")), Alias::new("day"))
+        .to_owned();
+    let days = query_all::<ShareDay>(&query, db).await?;
+    Ok(longest_streak(
+        days.into_iter().map(|row| row.day).collect(),
+    ))
+}
+
+/// Shares a user made in a chat since `since`, and how they were received.
+pub async fn user_share_stats(
+    telegram_channel_id: i64,
+    telegram_user_id: i64,
+    since: Option<DateTime<Utc>>,
+    db: &DatabaseConnection,
+) -> Result<ShareCounts> {
+    let filter = ShareFilter {
+        telegram_user_id: Some(telegram_user_id),
+        ..ShareFilter::chat(telegram_channel_id, since)
+    };
+    share_counts(&filter, db).await
+}
+
+/// Shares made in a chat since `since`, and how they were received.
+pub async fn chat_share_stats(
+    telegram_channel_id: i64,
+    since: Option<DateTime<Utc>>,
+    db: &DatabaseConnection,
+) -> Result<ShareCounts> {
+    share_counts(&ShareFilter::chat(telegram_channel_id, since), db).await
+}
+
+/// Users of a chat ordered by how many links they shared since `since`.
+pub async fn top_sharers(
+    telegram_channel_id: i64,
+    since: Option<DateTime<Utc>>,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<SharerStats>> {
+    let filter = ShareFilter::chat(telegram_channel_id, since);
+    sharers_by_shares(&filter, limit, db).await
+}
+
+/// Users of a chat ordered by the share of positive reactions to their links,
+/// counting only users with at least `min_rated_reactions`.
+pub async fn best_received_sharers(
+    telegram_channel_id: i64,
+    since: Option<DateTime<Utc>>,
+    min_rated_reactions: u64,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<SharerStats>> {
+    let positive_ratio =
+        Expr::expr(Func::cast_as(positive_reactions(), Alias::new("float"))).div(rated_reactions());
+    let mut query = sharers(&ShareFilter::chat(telegram_channel_id, since));
+    query
+        .and_having(Expr::expr(rated_reactions()).gte(min_rated_reactions.max(1)))
+        .order_by_expr(positive_ratio, Order::Desc)
+        .order_by_expr(positive_reactions(), Order::Desc)
+        .limit(limit);
+    query_all(&query, db).await
+}
+
+/// Tracks shared in a chat since `since` ordered by their positive reactions,
+/// leaving out tracks that received none.
+pub async fn top_tracks(
+    telegram_channel_id: i64,
+    since: Option<DateTime<Utc>>,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<TrackStats>> {
+    let filter = ShareFilter::chat(telegram_channel_id, since);
+    tracks_by_positive_reactions(&filter, limit, db).await
+}
+
+/// Tracks shared in a chat since `since` that split opinions, ordered by the
+/// smaller of their positive and negative reaction counts.
+pub async fn most_divisive_tracks(
+    telegram_channel_id: i64,
+    since: Option<DateTime<Utc>>,
+    limit: u64,
+    db: &DatabaseConnection,
+) -> Result<Vec<TrackStats>> {
+    let split = || SimpleExpr::from(Func::least([positive_reactions(), negative_reactions()]));
+    let mut query = tracks(&ShareFilter::chat(telegram_channel_id, since));
+    query
+        .and_having(Expr::expr(split()).gt(0))
+        .order_by_expr(split(), Order::Desc)
+        .order_by_expr(rated_reactions(), Order::Desc)
+        .limit(limit);
+    query_all(&query, db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use sea_orm::sea_query::PostgresQueryBuilder;
+
+    use super::*;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    fn counts(positive_reactions: i64, rated_reactions: i64) -> ShareCounts {
+        ShareCounts {
+            positive_reactions,
+            rated_reactions,
+            ..ShareCounts::default()
+        }
+    }
+
+    #[test]
+    fn positive_ratio_is_zero_without_rated_reactions() {
+        assert_eq!(counts(0, 0).positive_ratio(), 0.0);
+    }
+
+    #[test]
+    fn positive_ratio_counts_only_rated_reactions() {
+        assert_eq!(counts(3, 4).positive_ratio(), 0.75);
+    }
+
+    #[test]
+    fn longest_streak_is_none_without_shares() {
+        assert_eq!(longest_streak(vec![]), None);
+    }
+
+    #[test]
+    fn longest_streak_finds_the_longest_run() {
+        let streak = longest_streak(vec![day(1), day(2), day(5), day(6), day(7), day(9)]);
+        assert_eq!(
+            streak,
+            Some(ShareStreak {
+                days: 3,
+                first_day: day(5)
+            })
+        );
+    }
+
+    #[test]
+    fn longest_streak_ignores_order_and_repeated_days() {
+        let streak = longest_streak(vec![day(4), day(2), day(3), day(3), day(2)]);
+        assert_eq!(
+            streak,
+            Some(ShareStreak {
+                days: 3,
+                first_day: day(2)
+            })
+        );
+    }
+
+    #[test]
+    fn longest_streak_prefers_the_earliest_of_equal_runs() {
+        let streak = longest_streak(vec![day(10), day(11), day(1), day(2)]);
+        assert_eq!(
+            streak,
+            Some(ShareStreak {
+                days: 2,
+                first_day: day(1)
+            })
+        );
+    }
+
+    #[test]
+    fn longest_streak_spans_month_boundaries() {
+        let streak = longest_streak(vec![NaiveDate::from_ymd_opt(2025, 2, 28).unwrap(), day(1)]);
+        assert_eq!(streak.map(|streak| streak.days), Some(2));
+    }
+
+    fn sql(query: &SelectStatement) -> String {
+        query.to_string(PostgresQueryBuilder)
+    }
+
+    /// Renders a single select expression.
+    fn expr_sql(expr: SimpleExpr) -> String {
+        let sql = sql(&Query::select().expr(expr).to_owned());
+        sql.trim_start_matches("SELECT ").to_owned()
+    }
+
+    fn where_clause(query: &SelectStatement) -> String {
+        let sql = sql(query);
+        let (_, filter) = sql.split_once(" WHERE ").unwrap();
+        filter.to_owned()
+    }
+
+    #[test]
+    fn chat_filter_only_restricts_the_chat() {
+        let query = shares_with_reactions(&ShareFilter::chat(-100, None));
+        assert_eq!(
+            where_clause(&query),
+            "\"telegram_bot_channel\".\"telegram_channel_id\" = -100"
+        );
+    }
+
+    #[test]
+    fn full_filter_restricts_the_period_and_the_user() {
+        let filter = ShareFilter {
+            telegram_channel_id: -100,
+            telegram_user_id: Some(7),
+            since: Some(Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap()),
+            until: Some(Utc.with_ymd_and_hms(2025, 4, 1, 0, 0, 0).unwrap()),
+        };
+        assert_eq!(
+            where_clause(&shares_with_reactions(&filter)),
+            "\"telegram_bot_channel\".\"telegram_channel_id\" = -100 \
+             AND \"telegram_bot_music_share\".\"created_at\" >= '2025-03-01 00:00:00.000000 +00:00' \
+             AND \"telegram_bot_music_share\".\"created_at\" < '2025-04-01 00:00:00.000000 +00:00' \
+             AND \"telegram_bot_user\".\"telegram_user_id\" = 7"
+        );
+    }
+
+    #[test]
+    fn self_reactions_are_left_out_of_the_reaction_join() {
+        // Filtering them in the join rather than in WHERE keeps shares that
+        // only their sharer reacted to.
+        let sql = sql(&shares_with_reactions(&ShareFilter::chat(-100, None)));
+        let (_, reaction_join) = sql.split_once(" LEFT JOIN ").unwrap();
+        let (reaction_join, _) = reaction_join.split_once(" WHERE ").unwrap();
+        assert_eq!(
+            reaction_join,
+            "\"telegram_bot_music_share_reaction\" \
+             ON \"telegram_bot_music_share_reaction\".\"telegram_bot_music_share_id\" = \"telegram_bot_music_share\".\"id\" \
+             AND \"telegram_bot_music_share_reaction\".\"telegram_bot_user_id\" <> \"telegram_bot_music_share\".\"telegram_bot_user_id\""
+        );
+    }
+
+    #[test]
+    fn shares_are_counted_once_however_many_reactions_they_joined() {
+        assert_eq!(
+            expr_sql(shares()),
+            "COUNT(DISTINCT \"telegram_bot_music_share\".\"id\")"
+        );
+        assert_eq!(
+            expr_sql(reactions()),
+            "COUNT(\"telegram_bot_music_share_reaction\".\"id\")"
+        );
+    }
+
+    #[test]
+    fn mood_counts_only_count_their_moods() {
+        let count_of = |moods: &str| {
+            format!(
+                "COUNT((CASE WHEN (\"telegram_bot_music_share_reaction\".\"llm_sentiment_analysis\" \
+                 IN ({moods})) THEN \"telegram_bot_music_share_reaction\".\"id\" END))"
+            )
+        };
+        assert_eq!(expr_sql(positive_reactions()), count_of("'positive'"));
+        assert_eq!(expr_sql(negative_reactions()), count_of("'negative'"));
+        assert_eq!(
+            expr_sql(rated_reactions()),
+            count_of("'positive', 'negative', 'neutral'")
+        );
+    }
+
+    #[test]
+    fn sharers_and_tracks_group_by_what_they_rank() {
+        let filter = ShareFilter::chat(-100, None);
+        assert!(
+            sql(&sharers(&filter))
+                .ends_with(" GROUP BY \"telegram_bot_user\".\"telegram_user_id\"")
+        );
+        assert!(sql(&tracks(&filter)).ends_with(" GROUP BY \"music_link\".\"id\""));
+    }
+}
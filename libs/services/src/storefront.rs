@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use reqwest::Url;
+use rust_iso3166::from_alpha2;
+
+use crate::models::{MusicPlatform, PlatformLink};
+
+/// Storefront used when neither the user nor the chat picked one.
+pub static DEFAULT_COUNTRY: &str = "US";
+
+/// Returns the uppercase ISO 3166-1 alpha-2 code for `code`, if it is one.
+pub fn normalize_country_code(code: &str) -> Option<&'static str> {
+    from_alpha2(&code.trim().to_ascii_uppercase()).map(|country| country.alpha2)
+}
+
+/// Reads the region out of an IETF language tag such as `pt-br` or `en_GB`.
+///
+/// Tags without a region, like a plain `en`, say nothing about the storefront.
+pub fn country_from_language_code(language_code: &str) -> Option<&'static str> {
+    language_code
+        .split(['-', '_'])
+        .skip(1)
+        .find(|subtag| subtag.len() == 2)
+        .and_then(normalize_country_code)
+}
+
+/// Rewrites the storefront of a stored link to `country`, adding it to
+/// canonical links that were stored without one.
+///
+/// Only Apple Music keeps the storefront in its links, so every other link is
+/// returned unchanged.
+pub fn localize_link(platform: MusicPlatform, link: &str, country: &str) -> String {
+    if platform != MusicPlatform::AppleMusic {
+        return link.to_owned();
+    }
+    let Ok(mut url) = Url::parse(link) else {
+        return link.to_owned();
+    };
+    let Some(segments) = url.path_segments() else {
+        return link.to_owned();
+    };
+    let mut segments: Vec<String> = segments.map(str::to_owned).collect();
+    let storefront = country.to_ascii_lowercase();
+    match segments.first_mut() {
+        Some(existing) if existing.len() == 2 => *existing = storefront,
+        Some(_) => segments.insert(0, storefront),
+        None => return link.to_owned(),
+    }
+    url.set_path(&segments.join("/"));
+    url.to_string()
+}
+
+pub fn localize_platform_links(
+    platform_links: &HashMap<MusicPlatform, PlatformLink>,
+    country: &str,
+) -> HashMap<MusicPlatform, PlatformLink> {
+    platform_links
+        .iter()
+        .map(|(platform, found)| {
+            let link = PlatformLink {
+                link: localize_link(*platform, &found.link, country),
+                confidence: found.confidence,
+            };
+            (*platform, link)
+        })
+        .collect()
+}
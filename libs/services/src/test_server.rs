@@ -0,0 +1,11 @@
+use axum::Router;
+use tokio::net::TcpListener;
+
+/// Serves `router` on a free local port for the duration of the test, standing
+/// in for an upstream API. Returns the base url to point the client at.
+pub async fn serve(router: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{address}")
+}
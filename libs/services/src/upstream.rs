@@ -0,0 +1,290 @@
+use std::{
+    future::Future,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::UpstreamConfig,
+    error::{MusicLinkError, Result},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UpstreamStats {
+    pub name: &'static str,
+    pub requests: u64,
+    pub failures: u64,
+    pub rate_limited: u64,
+    pub short_circuited: u64,
+    pub circuit_open: bool,
+    pub consecutive_failures: u32,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+struct CircuitBreaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// When the single request allowed through a half-open circuit started.
+    trial_started_at: Option<Instant>,
+}
+
+/// Rate limits, retries and circuit-breaks calls to a single upstream API.
+///
+/// One guard should be shared by everything talking to the same upstream so
+/// that its limits apply process-wide.
+pub struct UpstreamGuard {
+    name: &'static str,
+    config: UpstreamConfig,
+    bucket: Mutex<TokenBucket>,
+    breaker: Mutex<CircuitBreaker>,
+    requests: AtomicU64,
+    failures: AtomicU64,
+    rate_limited: AtomicU64,
+    short_circuited: AtomicU64,
+}
+
+impl UpstreamGuard {
+    pub fn new(name: &'static str, config: UpstreamConfig) -> Self {
+        let bucket = TokenBucket {
+            tokens: f64::from(config.requests_per_minute),
+            last_refill: Instant::now(),
+        };
+        let breaker = CircuitBreaker {
+            consecutive_failures: 0,
+            opened_at: None,
+            trial_started_at: None,
+        };
+        Self {
+            name,
+            config,
+            bucket: Mutex::new(bucket),
+            breaker: Mutex::new(breaker),
+            requests: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            short_circuited: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> UpstreamStats {
+        let breaker = self.breaker.lock().unwrap();
+        UpstreamStats {
+            name: self.name,
+            requests: self.requests.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            short_circuited: self.short_circuited.load(Ordering::Relaxed),
+            circuit_open: breaker.opened_at.is_some(),
+            consecutive_failures: breaker.consecutive_failures,
+        }
+    }
+
+    /// Whether requests are currently being rejected without reaching upstream.
+    pub fn is_circuit_open(&self) -> bool {
+        self.breaker.lock().unwrap().opened_at.is_some()
+    }
+
+    /// Decides whether a request may go upstream. Once the circuit has been
+    /// open for long enough it is half-open, and a single trial request is let
+    /// through while every other caller is still rejected. A trial that never
+    /// reports back, e.g. because its caller was dropped, is replaced after
+    /// another `open_duration`.
+    fn try_admit(&self) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        let Some(opened_at) = breaker.opened_at else {
+            return true;
+        };
+        if opened_at.elapsed() < self.config.open_duration {
+            return false;
+        }
+        match breaker.trial_started_at {
+            Some(started) if started.elapsed() < self.config.open_duration => false,
+            _ => {
+                tracing::info!(
+                    "Circuit for {} half-open, allowing a trial request",
+                    self.name
+                );
+                breaker.trial_started_at = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        if breaker.opened_at.is_some() {
+            tracing::info!("Circuit for {} closed", self.name);
+        }
+        breaker.consecutive_failures = 0;
+        breaker.opened_at = None;
+        breaker.trial_started_at = None;
+    }
+
+    fn record_failure(&self, error: &MusicLinkError) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        if matches!(error, MusicLinkError::RateLimited { .. }) {
+            self.rate_limited.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures += 1;
+        if breaker.trial_started_at.take().is_some() {
+            tracing::warn!("Circuit for {} reopened after a failed trial", self.name);
+            breaker.opened_at = Some(Instant::now());
+        } else if breaker.opened_at.is_none()
+            && breaker.consecutive_failures >= self.config.failure_threshold
+        {
+            tracing::warn!(
+                "Circuit for {} opened after {} consecutive failures",
+                self.name,
+                breaker.consecutive_failures
+            );
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+
+    async fn acquire_token(&self) -> Result<()> {
+        let per_second = f64::from(self.config.requests_per_minute.max(1)) / 60.0;
+        let capacity = f64::from(self.config.requests_per_minute.max(1));
+        let started = Instant::now();
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let refilled = bucket.last_refill.elapsed().as_secs_f64() * per_second;
+                bucket.tokens = (bucket.tokens + refilled).min(capacity);
+                bucket.last_refill = Instant::now();
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return Ok(());
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / per_second)
+            };
+            if started.elapsed() + wait > self.config.max_queue_wait {
+                tracing::warn!("Rate limiter for {} is saturated", self.name);
+                self.rate_limited.fetch_add(1, Ordering::Relaxed);
+                return Err(MusicLinkError::RateLimited {
+                    retry_after: Some(wait),
+                });
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Runs `operation` once a rate limiter token is available, retrying
+    /// transient failures with exponential backoff or the upstream `Retry-After`.
+    pub async fn call<T, F, Fut>(&self, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            if !self.try_admit() {
+                self.short_circuited.fetch_add(1, Ordering::Relaxed);
+                return Err(MusicLinkError::UpstreamUnavailable(format!(
+                    "circuit for {} is open",
+                    self.name
+                )));
+            }
+            self.acquire_token().await?;
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let error = match operation().await {
+                Ok(value) => {
+                    self.record_success();
+                    return Ok(value);
+                }
+                Err(e) if !e.is_transient() => {
+                    self.record_success();
+                    return Err(e);
+                }
+                Err(e) => e,
+            };
+            self.record_failure(&error);
+            let backoff = match &error {
+                MusicLinkError::RateLimited {
+                    retry_after: Some(retry_after),
+                } => *retry_after,
+                _ => self.config.base_backoff * 2u32.saturating_pow(attempt),
+            };
+            if attempt >= self.config.max_retries || backoff > self.config.max_backoff {
+                return Err(error);
+            }
+            attempt += 1;
+            tracing::debug!(
+                "Retrying {} in {:?} (attempt {}): {}",
+                self.name,
+                backoff,
+                attempt,
+                error
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_circuit() -> UpstreamGuard {
+        let config = UpstreamConfig {
+            failure_threshold: 1,
+            open_duration: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let guard = UpstreamGuard::new("test", config);
+        guard.record_failure(&MusicLinkError::UpstreamUnavailable("down".to_owned()));
+        guard
+    }
+
+    #[test]
+    fn open_circuit_rejects_requests() {
+        let guard = open_circuit();
+
+        assert!(guard.is_circuit_open());
+        assert!(!guard.try_admit());
+    }
+
+    #[test]
+    fn half_open_circuit_lets_a_single_trial_through() {
+        let guard = open_circuit();
+        std::thread::sleep(Duration::from_millis(30));
+
+        assert!(guard.try_admit());
+        assert!(!guard.try_admit());
+        assert!(!guard.try_admit());
+    }
+
+    #[test]
+    fn successful_trial_closes_the_circuit() {
+        let guard = open_circuit();
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(guard.try_admit());
+
+        guard.record_success();
+
+        assert!(!guard.is_circuit_open());
+        assert!(guard.try_admit());
+        assert!(guard.try_admit());
+    }
+
+    #[test]
+    fn failed_trial_reopens_the_circuit() {
+        let guard = open_circuit();
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(guard.try_admit());
+
+        guard.record_failure(&MusicLinkError::UpstreamUnavailable("down".to_owned()));
+
+        assert!(guard.is_circuit_open());
+        assert!(!guard.try_admit());
+    }
+}
@@ -1,15 +1,19 @@
 use std::time::Duration;
 
 use reqwest::{
-    ClientBuilder,
-    header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT},
+    ClientBuilder, Response, StatusCode,
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER, USER_AGENT},
 };
 
+use crate::error::{MusicLinkError, Result};
+
 pub static USER_AGENT_STR: &str =
     "Muslink (https://github.com/ignisda/muslink) <ignisda2001@gmail.com>";
-pub static SONG_LINK_API_URL: &str = "https://api.song.link/v1-alpha.1/links";
 
-pub fn get_base_http_client(headers: Option<Vec<(HeaderName, HeaderValue)>>) -> reqwest::Client {
+pub fn get_base_http_client(
+    headers: Option<Vec<(HeaderName, HeaderValue)>>,
+    timeout: Duration,
+) -> reqwest::Client {
     let mut req_headers = HeaderMap::new();
     req_headers.insert(USER_AGENT, HeaderValue::from_static(USER_AGENT_STR));
     for (header, value) in headers.unwrap_or_default().into_iter() {
@@ -17,7 +21,52 @@ pub fn get_base_http_client(headers: Option<Vec<(HeaderName, HeaderValue)>>) ->
     }
     ClientBuilder::new()
         .default_headers(req_headers)
-        .timeout(Duration::from_secs(15))
+        .timeout(timeout)
         .build()
         .unwrap()
 }
+
+/// Escapes text for Telegram's HTML parse mode, which only needs `&`, `<` and
+/// `>` replaced outside of attributes.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Reads a `Retry-After` header expressed in seconds.
+pub fn get_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Maps unsuccessful upstream responses for `link` to a `MusicLinkError`.
+pub fn error_for_status(upstream: &str, response: Response, link: &str) -> Result<Response> {
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        StatusCode::TOO_MANY_REQUESTS => Err(MusicLinkError::RateLimited {
+            retry_after: get_retry_after(response.headers()),
+        }),
+        StatusCode::BAD_REQUEST => Err(MusicLinkError::UnsupportedUrl(link.to_owned())),
+        StatusCode::NOT_FOUND => Err(MusicLinkError::NotFound),
+        status => Err(MusicLinkError::UpstreamUnavailable(format!(
+            "{upstream} responded with {status}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_html_escapes_markup() {
+        assert_eq!(
+            escape_html("<b>Tom & Jerry</b>"),
+            "&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;"
+        );
+    }
+}
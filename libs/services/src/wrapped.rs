@@ -0,0 +1,499 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use chrono::{DateTime, Datelike, Month, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use embedded_graphics::{
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_9X15, FONT_9X15_BOLD, FONT_10X20},
+    },
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use entities::{
+    prelude::{TelegramBotChannel, TelegramBotChannelSettings},
+    telegram_bot_channel_settings,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+use crate::{
+    digest::parse_timezone,
+    error::Result,
+    stats::{
+        ArtistStats, ShareCounts, ShareFilter, ShareStats, ShareStreak, SharerStats, TrackStats,
+        artists_by_shares, longest_share_streak, share_counts, sharers_by_shares,
+        shares_by_reactions, tracks_by_positive_reactions,
+    },
+    utils::escape_html,
+};
+
+const FAVORITE_ARTISTS: u64 = 3;
+const CARD_WIDTH: u32 = 800;
+const CARD_MARGIN: i32 = 32;
+/// Characters of a value that fit on one line of the card.
+const CARD_LINE_CHARS: usize = (CARD_WIDTH as usize - 2 * CARD_MARGIN as usize) / 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrappedPeriod {
+    Year(i32),
+    Month(i32, u32),
+}
+
+impl WrappedPeriod {
+    /// Reads a period written as `2025` or `2025-03`, rejecting years the
+    /// calendar cannot hold.
+    pub fn parse(period: &str) -> Option<Self> {
+        let period = period.trim();
+        let parsed = match period.split_once('-') {
+            None => Self::Year(period.parse().ok()?),
+            Some((year, month)) => {
+                let month = month
+                    .parse()
+                    .ok()
+                    .filter(|month| (1..=12).contains(month))?;
+                Self::Month(year.parse().ok()?, month)
+            }
+        };
+        parsed.bounds(Tz::UTC).map(|_| parsed)
+    }
+
+    /// The period recapped at the start of the month `today` falls in: the
+    /// past year in January, and the past month otherwise.
+    pub fn previous(today: NaiveDate) -> Self {
+        match today.month() {
+            1 => Self::Year(today.year() - 1),
+            month => Self::Month(today.year(), month - 1),
+        }
+    }
+
+    fn first_day(self) -> Option<NaiveDate> {
+        match self {
+            Self::Year(year) => NaiveDate::from_ymd_opt(year, 1, 1),
+            Self::Month(year, month) => NaiveDate::from_ymd_opt(year, month, 1),
+        }
+    }
+
+    fn next(self) -> Option<Self> {
+        let next = match self {
+            Self::Year(year) => Self::Year(year.checked_add(1)?),
+            Self::Month(year, 12) => Self::Month(year.checked_add(1)?, 1),
+            Self::Month(year, month) => Self::Month(year, month + 1),
+        };
+        Some(next)
+    }
+
+    /// Start and end of the period in `timezone`.
+    fn bounds(self, timezone: Tz) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start_of = |date: NaiveDate| {
+            let midnight = date.and_time(NaiveTime::MIN);
+            timezone
+                .from_local_datetime(&midnight)
+                .earliest()
+                .map(|start| start.with_timezone(&Utc))
+                .unwrap_or_else(|| midnight.and_utc())
+        };
+        let start = start_of(self.first_day()?);
+        let end = start_of(self.next()?.first_day()?);
+        Some((start, end))
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Self::Year(year) => year.to_string(),
+            Self::Month(year, month) => match Month::try_from(month as u8) {
+                Ok(name) => format!("{} {}", name.name(), year),
+                Err(_) => format!("{year}-{month:02}"),
+            },
+        }
+    }
+}
+
+/// Highlights of what a chat, or one user in it, shared over a period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrappedRecap {
+    pub period: WrappedPeriod,
+    /// The user the recap is for, or `None` for the whole chat.
+    pub telegram_user_id: Option<i64>,
+    pub counts: ShareCounts,
+    pub favorite_artists: Vec<ArtistStats>,
+    pub best_received_track: Option<TrackStats>,
+    pub longest_streak: Option<ShareStreak>,
+    pub most_reacted_share: Option<ShareStats>,
+    /// Only set for chat recaps.
+    pub top_sharer: Option<SharerStats>,
+}
+
+impl WrappedRecap {
+    fn empty(period: WrappedPeriod, telegram_user_id: Option<i64>) -> Self {
+        Self {
+            period,
+            telegram_user_id,
+            counts: ShareCounts::default(),
+            favorite_artists: vec![],
+            best_received_track: None,
+            longest_streak: None,
+            most_reacted_share: None,
+            top_sharer: None,
+        }
+    }
+
+    /// Users named in the recap, whose names are needed to format it.
+    pub fn telegram_user_ids(&self) -> Vec<i64> {
+        let mut ids: Vec<i64> = [
+            self.telegram_user_id,
+            self.most_reacted_share.as_ref().map(|s| s.telegram_user_id),
+            self.top_sharer.as_ref().map(|s| s.telegram_user_id),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+}
+
+pub async fn wrapped_recap(
+    telegram_channel_id: i64,
+    telegram_user_id: Option<i64>,
+    period: WrappedPeriod,
+    timezone: Tz,
+    db: &DatabaseConnection,
+) -> Result<WrappedRecap> {
+    let Some((since, until)) = period.bounds(timezone) else {
+        // A period outside the calendar holds no shares, rather than all of them.
+        return Ok(WrappedRecap::empty(period, telegram_user_id));
+    };
+    let filter = ShareFilter {
+        telegram_channel_id,
+        telegram_user_id,
+        since: Some(since),
+        until: Some(until),
+    };
+    let counts = share_counts(&filter, db).await?;
+    let favorite_artists = artists_by_shares(&filter, FAVORITE_ARTISTS, db).await?;
+    let best_received_track = tracks_by_positive_reactions(&filter, 1, db).await?.pop();
+    let longest_streak = longest_share_streak(&filter, timezone.name(), db).await?;
+    let most_reacted_share = shares_by_reactions(&filter, 1, db).await?.pop();
+    let top_sharer = match telegram_user_id {
+        Some(_) => None,
+        None => sharers_by_shares(&filter, 1, db).await?.pop(),
+    };
+    Ok(WrappedRecap {
+        period,
+        telegram_user_id,
+        counts,
+        favorite_artists,
+        best_received_track,
+        longest_streak,
+        most_reacted_share,
+        top_sharer,
+    })
+}
+
+/// Joined with a plain hyphen, since the card fonts have no dashes.
+fn track_name(title: Option<&str>, artists: &[String]) -> String {
+    let title = title.unwrap_or("Unknown title");
+    match artists.is_empty() {
+        true => title.to_owned(),
+        false => format!("{} - {}", artists.join(", "), title),
+    }
+}
+
+/// The recap as a title and labelled lines of plain text, shared by the
+/// message and the card.
+fn recap_lines(
+    recap: &WrappedRecap,
+    names: &HashMap<i64, String>,
+) -> (String, Vec<(&'static str, String)>) {
+    let name = |id: i64| {
+        names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| "Someone who left".to_owned())
+    };
+    let title = match recap.telegram_user_id {
+        Some(id) => format!("Wrapped {} for {}", recap.period.label(), name(id)),
+        None => format!("Wrapped {}", recap.period.label()),
+    };
+    let mut lines = vec![(
+        "Shares",
+        format!(
+            "{} links, {} reactions received",
+            recap.counts.shares, recap.counts.reactions
+        ),
+    )];
+    if !recap.favorite_artists.is_empty() {
+        let artists: Vec<_> = recap
+            .favorite_artists
+            .iter()
+            .map(|artist| format!("{} ({})", artist.artist, artist.shares))
+            .collect();
+        lines.push(("Favorite artists", artists.join(", ")));
+    }
+    if let Some(track) = &recap.best_received_track {
+        lines.push((
+            "Best received",
+            format!(
+                "{}, {} positive reactions",
+                track_name(track.title.as_deref(), &track.artists),
+                track.counts.positive_reactions
+            ),
+        ));
+    }
+    if let Some(streak) = &recap.longest_streak {
+        lines.push((
+            "Longest streak",
+            format!("{} days from {}", streak.days, streak.first_day),
+        ));
+    }
+    if let Some(share) = &recap.most_reacted_share {
+        lines.push((
+            "Most reacted",
+            format!(
+                "{} by {}, {} reactions",
+                track_name(share.title.as_deref(), &share.artists),
+                name(share.telegram_user_id),
+                share.reactions
+            ),
+        ));
+    }
+    if let Some(sharer) = &recap.top_sharer {
+        lines.push((
+            "Top sharer",
+            format!(
+                "{} with {} links",
+                name(sharer.telegram_user_id),
+                sharer.counts.shares
+            ),
+        ));
+    }
+    (title, lines)
+}
+
+/// Formats a recap as an HTML message, with `names` mapping the users in
+/// `WrappedRecap::telegram_user_ids` to their display names.
+pub fn format_wrapped(recap: &WrappedRecap, names: &HashMap<i64, String>) -> String {
+    let (title, lines) = recap_lines(recap, names);
+    if recap.counts.shares == 0 {
+        return format!("<b>{}</b>\nNothing shared yet.", escape_html(&title));
+    }
+    let mut text = format!("<b>{}</b>", escape_html(&title));
+    for (label, value) in lines {
+        text.push_str(&format!("\n\n<b>{}</b>\n{}", label, escape_html(&value)));
+    }
+    text
+}
+
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgb888>,
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> std::result::Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y))
+                && x < self.width
+                && y < self.height
+            {
+                self.pixels[(y * self.width + x) as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `text` into lines of at most `width` characters, at spaces where
+/// possible.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split(' ') {
+        if !line.is_empty() && line.chars().count() + word.chars().count() >= width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+        .into_iter()
+        .flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            chars
+                .chunks(width)
+                .map(|chunk| chunk.iter().collect::<String>())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Renders a recap as a PNG card. The built-in fonts only cover Latin-1, so
+/// other characters are drawn as `?`. Returns `None` if encoding fails.
+pub fn render_wrapped_card(recap: &WrappedRecap, names: &HashMap<i64, String>) -> Option<Vec<u8>> {
+    let (title, lines) = recap_lines(recap, names);
+    let mut rows = vec![];
+    for (label, value) in &lines {
+        let wrapped = wrap_text(value, CARD_LINE_CHARS);
+        rows.push((*label, wrapped));
+    }
+    let body_lines: usize = rows.iter().map(|(_, wrapped)| 1 + wrapped.len()).sum();
+    let height = (CARD_MARGIN * 2 + 40 + body_lines as i32 * 18 + rows.len() as i32 * 12) as u32;
+
+    let background = Rgb888::new(24, 24, 32);
+    let accent = Rgb888::new(30, 215, 96);
+    let mut canvas = Canvas {
+        width: CARD_WIDTH,
+        height,
+        pixels: vec![background; (CARD_WIDTH * height) as usize],
+    };
+    let _ = Rectangle::new(Point::zero(), Size::new(CARD_WIDTH, 8))
+        .into_styled(PrimitiveStyle::with_fill(accent))
+        .draw(&mut canvas);
+    let title_style = MonoTextStyle::new(&FONT_10X20, Rgb888::WHITE);
+    let label_style = MonoTextStyle::new(&FONT_9X15_BOLD, accent);
+    let value_style = MonoTextStyle::new(&FONT_9X15, Rgb888::WHITE);
+    let mut y = CARD_MARGIN;
+    let _ = Text::with_baseline(
+        &title,
+        Point::new(CARD_MARGIN, y),
+        title_style,
+        Baseline::Top,
+    )
+    .draw(&mut canvas);
+    y += 40;
+    for (label, wrapped) in rows {
+        let _ = Text::with_baseline(
+            label,
+            Point::new(CARD_MARGIN, y),
+            label_style,
+            Baseline::Top,
+        )
+        .draw(&mut canvas);
+        y += 18;
+        for line in wrapped {
+            let _ = Text::with_baseline(
+                &line,
+                Point::new(CARD_MARGIN, y),
+                value_style,
+                Baseline::Top,
+            )
+            .draw(&mut canvas);
+            y += 18;
+        }
+        y += 12;
+    }
+
+    let data: Vec<u8> = canvas
+        .pixels
+        .iter()
+        .flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b()])
+        .collect();
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, canvas.width, canvas.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let encoded = encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data));
+    if let Err(e) = encoded {
+        tracing::warn!("Failed to encode wrapped card: {}", e);
+        return None;
+    }
+    Some(png)
+}
+
+/// A chat that gets the recap of each period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrappedChat {
+    pub telegram_channel_id: i64,
+    /// The timezone the chat's periods are counted in.
+    pub timezone: Tz,
+}
+
+/// Chats that opted into the recaps.
+pub async fn wrapped_chats(db: &DatabaseConnection) -> Result<Vec<WrappedChat>> {
+    let opted_in = TelegramBotChannelSettings::find()
+        .filter(telegram_bot_channel_settings::Column::WrappedEnabled.eq(true))
+        .find_also_related(TelegramBotChannel)
+        .all(db)
+        .await?;
+    let chats = opted_in
+        .into_iter()
+        .filter_map(|(settings, channel)| {
+            Some(WrappedChat {
+                telegram_channel_id: channel?.telegram_channel_id,
+                timezone: parse_timezone(&settings.timezone).unwrap_or(Tz::UTC),
+            })
+        })
+        .collect();
+    Ok(chats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_years_and_months() {
+        assert_eq!(
+            WrappedPeriod::parse("2025"),
+            Some(WrappedPeriod::Year(2025))
+        );
+        assert_eq!(
+            WrappedPeriod::parse(" 2025-03 "),
+            Some(WrappedPeriod::Month(2025, 3))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_months_outside_the_year() {
+        assert_eq!(WrappedPeriod::parse("2025-00"), None);
+        assert_eq!(WrappedPeriod::parse("2025-13"), None);
+    }
+
+    #[test]
+    fn parse_rejects_years_outside_the_calendar() {
+        assert_eq!(WrappedPeriod::parse("999999"), None);
+        assert_eq!(WrappedPeriod::parse("2147483647"), None);
+        assert_eq!(WrappedPeriod::parse("999999-12"), None);
+    }
+
+    #[test]
+    fn bounds_cover_the_period_in_the_timezone() {
+        let (start, end) = WrappedPeriod::Month(2025, 12)
+            .bounds(chrono_tz::Europe::Berlin)
+            .unwrap();
+        assert_eq!(start.to_rfc3339(), "2025-11-30T23:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2025-12-31T23:00:00+00:00");
+    }
+
+    #[test]
+    fn previous_recaps_the_past_year_in_january() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        assert_eq!(WrappedPeriod::previous(today), WrappedPeriod::Year(2025));
+        let today = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        assert_eq!(
+            WrappedPeriod::previous(today),
+            WrappedPeriod::Month(2026, 2)
+        );
+    }
+}